@@ -0,0 +1,15 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use pallet_did::DidDocument;
+use sp_std::prelude::*;
+
+sp_api::decl_runtime_apis! {
+    pub trait DidApi<AccountId, BlockNumber>
+    where
+        AccountId: Codec,
+        BlockNumber: Codec,
+    {
+        fn resolve(did: Vec<u8>) -> Option<DidDocument<AccountId, BlockNumber>>;
+    }
+}
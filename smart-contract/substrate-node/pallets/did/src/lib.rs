@@ -0,0 +1,204 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A registry of DID documents, so farms and nodes can be identified on chain
+//! instead of through the explorer.
+
+use codec::{Decode, Encode};
+use frame_support::{
+    debug, decl_error, decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure,
+    traits::Get,
+};
+use frame_system::{self as system, ensure_signed};
+use sp_runtime::RuntimeDebug;
+use sp_std::prelude::*;
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+pub const DID_PREFIX: &[u8] = b"did:";
+pub const MAX_VERIFICATION_METHODS: usize = 16;
+pub const MAX_SERVICES: usize = 16;
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub enum KeyType {
+    Ed25519,
+    Sr25519,
+}
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub struct VerificationMethod {
+    pub id: Vec<u8>,
+    pub key_type: KeyType,
+    pub public_key: [u8; 32],
+}
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub struct ServiceEndpoint {
+    pub id: Vec<u8>,
+    pub service_type: Vec<u8>,
+    pub endpoint: Vec<u8>,
+}
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub struct DidDocument<AccountId, BlockNumber> {
+    pub controller: AccountId,
+    pub verification_methods: Vec<VerificationMethod>,
+    pub services: Vec<ServiceEndpoint>,
+    pub active: bool,
+    pub updated: BlockNumber,
+}
+
+impl<AccountId, BlockNumber> DidDocument<AccountId, BlockNumber> {
+    pub fn service(&self, service_type: &[u8]) -> Option<&ServiceEndpoint> {
+        self.services
+            .iter()
+            .find(|service| service.service_type.as_slice() == service_type)
+    }
+
+    pub fn key(&self, key_type: KeyType) -> Option<[u8; 32]> {
+        self.verification_methods
+            .iter()
+            .find(|method| method.key_type == key_type)
+            .map(|method| method.public_key)
+    }
+}
+
+pub trait Trait: system::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as DidModule {
+        pub Documents get(fn documents): map hasher(blake2_128_concat) Vec<u8> => Option<DidDocument<T::AccountId, T::BlockNumber>>;
+    }
+}
+
+decl_event!(
+    pub enum Event<T>
+    where
+        AccountId = <T as frame_system::Trait>::AccountId,
+    {
+        DidCreated(Vec<u8>, AccountId),
+        DidUpdated(Vec<u8>),
+        DidDeactivated(Vec<u8>),
+    }
+);
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        InvalidDid,
+        DidExists,
+        DidNotExists,
+        DidDeactivated,
+        UnauthorizedController,
+        TooManyVerificationMethods,
+        TooManyServices,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn create_did(origin, did: Vec<u8>, verification_methods: Vec<VerificationMethod>, services: Vec<ServiceEndpoint>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(did.starts_with(DID_PREFIX), Error::<T>::InvalidDid);
+            ensure!(!Documents::<T>::contains_key(&did), Error::<T>::DidExists);
+            Self::validate(&verification_methods, &services)?;
+
+            let document = DidDocument {
+                controller: who.clone(),
+                verification_methods,
+                services,
+                active: true,
+                updated: <system::Module<T>>::block_number(),
+            };
+            debug::info!("Creating DID: {:?}", did);
+
+            Documents::<T>::insert(&did, &document);
+
+            Self::deposit_event(RawEvent::DidCreated(did, who));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn update_did(origin, did: Vec<u8>, verification_methods: Vec<VerificationMethod>, services: Vec<ServiceEndpoint>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let mut document = Self::controlled_document(&did, &who)?;
+            Self::validate(&verification_methods, &services)?;
+
+            document.verification_methods = verification_methods;
+            document.services = services;
+            document.updated = <system::Module<T>>::block_number();
+
+            Documents::<T>::insert(&did, &document);
+
+            Self::deposit_event(RawEvent::DidUpdated(did));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn deactivate_did(origin, did: Vec<u8>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let mut document = Self::controlled_document(&did, &who)?;
+
+            // Deactivated documents are kept so they keep resolving, but can no longer be used
+            document.active = false;
+            document.verification_methods = Vec::new();
+            document.services = Vec::new();
+            document.updated = <system::Module<T>>::block_number();
+
+            Documents::<T>::insert(&did, &document);
+
+            Self::deposit_event(RawEvent::DidDeactivated(did));
+
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Resolve a DID to its document, including deactivated ones.
+    pub fn resolve(did: &[u8]) -> Option<DidDocument<T::AccountId, T::BlockNumber>> {
+        Documents::<T>::get(did)
+    }
+
+    /// Resolve a DID to its document, only if it is still active.
+    pub fn resolve_active(did: &[u8]) -> Option<DidDocument<T::AccountId, T::BlockNumber>> {
+        Self::resolve(did).filter(|document| document.active)
+    }
+
+    fn controlled_document(
+        did: &[u8],
+        who: &T::AccountId,
+    ) -> Result<DidDocument<T::AccountId, T::BlockNumber>, Error<T>> {
+        let document = Documents::<T>::get(did).ok_or(Error::<T>::DidNotExists)?;
+        ensure!(&document.controller == who, Error::<T>::UnauthorizedController);
+        ensure!(document.active, Error::<T>::DidDeactivated);
+
+        Ok(document)
+    }
+
+    fn validate(
+        verification_methods: &[VerificationMethod],
+        services: &[ServiceEndpoint],
+    ) -> Result<(), Error<T>> {
+        ensure!(
+            verification_methods.len() <= MAX_VERIFICATION_METHODS,
+            Error::<T>::TooManyVerificationMethods
+        );
+        ensure!(services.len() <= MAX_SERVICES, Error::<T>::TooManyServices);
+
+        Ok(())
+    }
+}
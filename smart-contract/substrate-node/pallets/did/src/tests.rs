@@ -0,0 +1,164 @@
+use super::*;
+use crate::mock::*;
+use frame_support::{assert_noop, assert_ok};
+
+const CONTROLLER: u64 = 1;
+const OTHER: u64 = 2;
+
+fn did() -> Vec<u8> {
+    b"did:farm:1".to_vec()
+}
+
+fn key(key_type: KeyType, seed: u8) -> VerificationMethod {
+    VerificationMethod {
+        id: b"key-1".to_vec(),
+        key_type,
+        public_key: [seed; 32],
+    }
+}
+
+fn service(service_type: &[u8]) -> ServiceEndpoint {
+    ServiceEndpoint {
+        id: b"service-1".to_vec(),
+        service_type: service_type.to_vec(),
+        endpoint: b"https://farm.example".to_vec(),
+    }
+}
+
+fn create() {
+    assert_ok!(DidModule::create_did(
+        Origin::signed(CONTROLLER),
+        did(),
+        vec![key(KeyType::Ed25519, 1)],
+        vec![service(b"pricing")]
+    ));
+}
+
+#[test]
+fn a_did_is_created_once_by_its_controller() {
+    new_test_ext().execute_with(|| {
+        create();
+
+        let document = DidModule::resolve(&did()).unwrap();
+        assert_eq!(document.controller, CONTROLLER);
+        assert!(document.active);
+        assert_eq!(document.updated, 1);
+        assert_eq!(document.key(KeyType::Ed25519), Some([1; 32]));
+        assert_eq!(document.key(KeyType::Sr25519), None);
+        assert_eq!(document.service(b"pricing"), Some(&service(b"pricing")));
+
+        assert_noop!(
+            DidModule::create_did(Origin::signed(OTHER), did(), vec![], vec![]),
+            Error::<Test>::DidExists
+        );
+    });
+}
+
+#[test]
+fn a_did_needs_the_prefix_and_bounded_contents() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            DidModule::create_did(
+                Origin::signed(CONTROLLER),
+                b"farm:1".to_vec(),
+                vec![],
+                vec![]
+            ),
+            Error::<Test>::InvalidDid
+        );
+
+        let keys = vec![key(KeyType::Ed25519, 1); MAX_VERIFICATION_METHODS + 1];
+        assert_noop!(
+            DidModule::create_did(Origin::signed(CONTROLLER), did(), keys, vec![]),
+            Error::<Test>::TooManyVerificationMethods
+        );
+
+        let services = vec![service(b"pricing"); MAX_SERVICES + 1];
+        assert_noop!(
+            DidModule::create_did(Origin::signed(CONTROLLER), did(), vec![], services),
+            Error::<Test>::TooManyServices
+        );
+    });
+}
+
+#[test]
+fn only_the_controller_updates_a_did() {
+    new_test_ext().execute_with(|| {
+        create();
+        System::set_block_number(5);
+
+        assert_noop!(
+            DidModule::update_did(Origin::signed(OTHER), did(), vec![], vec![]),
+            Error::<Test>::UnauthorizedController
+        );
+        assert_noop!(
+            DidModule::update_did(
+                Origin::signed(CONTROLLER),
+                b"did:farm:2".to_vec(),
+                vec![],
+                vec![]
+            ),
+            Error::<Test>::DidNotExists
+        );
+
+        assert_ok!(DidModule::update_did(
+            Origin::signed(CONTROLLER),
+            did(),
+            vec![key(KeyType::Sr25519, 2)],
+            vec![]
+        ));
+        let document = DidModule::resolve(&did()).unwrap();
+        assert_eq!(document.key(KeyType::Ed25519), None);
+        assert_eq!(document.key(KeyType::Sr25519), Some([2; 32]));
+        assert_eq!(document.service(b"pricing"), None);
+        assert_eq!(document.updated, 5);
+    });
+}
+
+#[test]
+fn a_deactivated_did_still_resolves_but_is_no_longer_active() {
+    new_test_ext().execute_with(|| {
+        create();
+
+        assert_noop!(
+            DidModule::deactivate_did(Origin::signed(OTHER), did()),
+            Error::<Test>::UnauthorizedController
+        );
+        assert_ok!(DidModule::deactivate_did(Origin::signed(CONTROLLER), did()));
+
+        // It keeps resolving, without the keys and services it can no longer be used for
+        let document = DidModule::resolve(&did()).unwrap();
+        assert!(!document.active);
+        assert!(document.verification_methods.is_empty());
+        assert!(document.services.is_empty());
+        assert_eq!(DidModule::resolve_active(&did()), None);
+
+        assert_noop!(
+            DidModule::update_did(Origin::signed(CONTROLLER), did(), vec![], vec![]),
+            Error::<Test>::DidDeactivated
+        );
+        assert_noop!(
+            DidModule::deactivate_did(Origin::signed(CONTROLLER), did()),
+            Error::<Test>::DidDeactivated
+        );
+        // The DID can't be taken over by creating it again
+        assert_noop!(
+            DidModule::create_did(Origin::signed(OTHER), did(), vec![], vec![]),
+            Error::<Test>::DidExists
+        );
+    });
+}
+
+#[test]
+fn unknown_dids_do_not_resolve() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(DidModule::resolve(&did()), None);
+        assert_eq!(DidModule::resolve_active(&did()), None);
+
+        create();
+        assert_eq!(
+            DidModule::resolve_active(&did()),
+            DidModule::resolve(&did())
+        );
+    });
+}
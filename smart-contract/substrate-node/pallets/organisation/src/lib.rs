@@ -0,0 +1,238 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Organisations holding a shared balance that their members spend on contracts,
+//! each member within the limits of its role and spending limit.
+
+use codec::{Decode, Encode};
+use frame_support::{
+    debug, decl_error, decl_event, decl_module, decl_storage,
+    dispatch::{DispatchError, DispatchResult},
+    ensure,
+    traits::{Currency, ExistenceRequirement::AllowDeath, Get},
+};
+use frame_system::{self as system, ensure_signed};
+use sp_runtime::{
+    traits::{AccountIdConversion, Saturating},
+    ModuleId, RuntimeDebug,
+};
+use sp_std::prelude::*;
+
+const PALLET_ID: ModuleId = ModuleId(*b"tf/orgs_");
+
+pub type BalanceOf<T> =
+    <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+
+#[derive(PartialEq, Eq, Clone, Copy, Encode, Decode, RuntimeDebug)]
+pub enum Role {
+    // Manages members and the organisation's balance
+    Admin,
+    // Creates and funds contracts
+    Deployer,
+    Viewer,
+}
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub struct Member<Balance> {
+    pub role: Role,
+    // None lets the member spend without limit
+    pub spending_limit: Option<Balance>,
+    pub spent: Balance,
+}
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct Organisation {
+    pub name: Vec<u8>,
+}
+
+pub trait Trait: system::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+    type Currency: Currency<Self::AccountId>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as OrganisationModule {
+        pub Organisations get(fn organisations): map hasher(blake2_128_concat) u64 => Option<Organisation>;
+        pub Members get(fn members): double_map hasher(blake2_128_concat) u64, hasher(blake2_128_concat) T::AccountId => Option<Member<BalanceOf<T>>>;
+        OrganisationID: u64;
+        // Admins of an organisation, kept so checking for the last one doesn't go over all members
+        AdminCount get(fn admin_count): map hasher(blake2_128_concat) u64 => u32;
+    }
+}
+
+decl_event!(
+    pub enum Event<T>
+    where
+        AccountId = <T as frame_system::Trait>::AccountId,
+        Balance = BalanceOf<T>,
+    {
+        OrganisationCreated(u64, AccountId),
+        MemberSet(u64, AccountId, Role),
+        MemberRemoved(u64, AccountId),
+        OrganisationFunded(u64, AccountId, Balance),
+        OrganisationWithdrawn(u64, AccountId, Balance),
+        // Will signal a member spending part of the organisation's balance
+        OrganisationSpent(u64, AccountId, Balance),
+    }
+);
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        OrganisationNotExists,
+        NotAMember,
+        UnauthorizedMember,
+        SpendingLimitExceeded,
+        LastAdmin,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,4)]
+        pub fn create_organisation(origin, name: Vec<u8>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let org_id = OrganisationID::get();
+            debug::info!("Creating organisation: {:?} with id: {:?}", name, org_id);
+
+            Organisations::insert(org_id, Organisation { name });
+            Members::<T>::insert(org_id, &who, Member {
+                role: Role::Admin,
+                spending_limit: None,
+                spent: 0u32.into(),
+            });
+            AdminCount::insert(org_id, 1);
+            OrganisationID::put(org_id + 1);
+
+            Self::deposit_event(RawEvent::OrganisationCreated(org_id, who));
+
+            Ok(())
+        }
+
+        // Add a member or change its role and limit, what it already spent is kept
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(4,2)]
+        pub fn set_member(origin, org_id: u64, member: T::AccountId, role: Role, spending_limit: Option<BalanceOf<T>>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            Self::ensure_role(org_id, &who, &[Role::Admin])?;
+            if role != Role::Admin {
+                Self::ensure_not_last_admin(org_id, &member)?;
+            }
+
+            let previous = Members::<T>::get(org_id, &member);
+            let was_admin = previous.as_ref().map(|member| member.role) == Some(Role::Admin);
+            match (was_admin, role == Role::Admin) {
+                (false, true) => AdminCount::mutate(org_id, |count| *count += 1),
+                (true, false) => AdminCount::mutate(org_id, |count| *count -= 1),
+                _ => (),
+            }
+
+            let spent = previous.map(|member| member.spent).unwrap_or(0u32.into());
+            Members::<T>::insert(org_id, &member, Member { role, spending_limit, spent });
+
+            Self::deposit_event(RawEvent::MemberSet(org_id, member, role));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(4,2)]
+        pub fn remove_member(origin, org_id: u64, member: T::AccountId) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            Self::ensure_role(org_id, &who, &[Role::Admin])?;
+            let removed = Members::<T>::get(org_id, &member).ok_or(Error::<T>::NotAMember)?;
+            Self::ensure_not_last_admin(org_id, &member)?;
+
+            if removed.role == Role::Admin {
+                AdminCount::mutate(org_id, |count| *count -= 1);
+            }
+            Members::<T>::remove(org_id, &member);
+
+            Self::deposit_event(RawEvent::MemberRemoved(org_id, member));
+
+            Ok(())
+        }
+
+        // Anyone can add funds to an organisation
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,2)]
+        pub fn fund_organisation(origin, org_id: u64, amount: BalanceOf<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(Organisations::contains_key(org_id), Error::<T>::OrganisationNotExists);
+
+            T::Currency::transfer(&who, &Self::org_account(org_id), amount, AllowDeath)?;
+
+            Self::deposit_event(RawEvent::OrganisationFunded(org_id, who, amount));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,2)]
+        pub fn withdraw_organisation(origin, org_id: u64, amount: BalanceOf<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            Self::ensure_role(org_id, &who, &[Role::Admin])?;
+
+            T::Currency::transfer(&Self::org_account(org_id), &who, amount, AllowDeath)?;
+
+            Self::deposit_event(RawEvent::OrganisationWithdrawn(org_id, who, amount));
+
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// The account holding an organisation's balance, it owns the organisation's contracts.
+    pub fn org_account(org_id: u64) -> T::AccountId {
+        PALLET_ID.into_sub_account(org_id)
+    }
+
+    /// Ensure `who` is a member of the organisation with one of the given roles.
+    pub fn ensure_role(
+        org_id: u64,
+        who: &T::AccountId,
+        roles: &[Role],
+    ) -> Result<Member<BalanceOf<T>>, DispatchError> {
+        ensure!(Organisations::contains_key(org_id), Error::<T>::OrganisationNotExists);
+        let member = Members::<T>::get(org_id, who).ok_or(Error::<T>::NotAMember)?;
+        ensure!(roles.contains(&member.role), Error::<T>::UnauthorizedMember);
+
+        Ok(member)
+    }
+
+    /// Ensure a member allowed to deploy can spend `amount` within its spending limit.
+    pub fn ensure_can_spend(org_id: u64, who: &T::AccountId, amount: BalanceOf<T>) -> DispatchResult {
+        let member = Self::ensure_role(org_id, who, &[Role::Admin, Role::Deployer])?;
+
+        if let Some(limit) = member.spending_limit {
+            ensure!(member.spent.saturating_add(amount) <= limit, Error::<T>::SpendingLimitExceeded);
+        }
+
+        Ok(())
+    }
+
+    /// Count `amount` against a member's spending limit, once it was spent.
+    pub fn note_spent(org_id: u64, who: &T::AccountId, amount: BalanceOf<T>) {
+        Members::<T>::mutate(org_id, who, |member| {
+            if let Some(member) = member {
+                member.spent = member.spent.saturating_add(amount);
+            }
+        });
+
+        Self::deposit_event(RawEvent::OrganisationSpent(org_id, who.clone(), amount));
+    }
+
+    // An organisation always keeps an admin to manage it
+    fn ensure_not_last_admin(org_id: u64, member: &T::AccountId) -> DispatchResult {
+        let is_admin = Members::<T>::get(org_id, member).map(|m| m.role) == Some(Role::Admin);
+        if is_admin {
+            ensure!(AdminCount::get(org_id) > 1, Error::<T>::LastAdmin);
+        }
+
+        Ok(())
+    }
+}
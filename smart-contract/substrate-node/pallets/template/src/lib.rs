@@ -9,30 +9,38 @@ use codec::{Decode, Encode};
 use core::{convert::TryInto, fmt};
 use frame_support::{
     debug, decl_error, decl_event, decl_module, decl_storage,
-    dispatch::{DispatchError, DispatchResult},
-    ensure,
+    dispatch::{DispatchError, DispatchResult, DispatchResultWithPostInfo},
+    ensure, transactional,
     sp_runtime::{
         offchain as rt_offchain,
         offchain::{
             storage::StorageValueRef,
             storage_lock::{BlockAndTime, StorageLock},
         },
-        traits::AccountIdConversion,
+        traits::{AccountIdConversion, Verify},
         traits::SaturatedConversion,
-        ModuleId,
+        traits::{Saturating, Zero},
+        transaction_validity::{
+            InvalidTransaction, TransactionSource, TransactionValidity, ValidTransaction,
+        },
+        ModuleId, Perbill,
+    },
+    traits::{
+        BalanceStatus, Currency, EnsureOrigin, ExistenceRequirement::{AllowDeath, KeepAlive}, Get, Imbalance,
+        Randomness, ReservableCurrency, WithdrawReason, WithdrawReasons,
     },
-    traits::{Currency, ExistenceRequirement::AllowDeath, Get, Randomness},
+    weights::Weight,
 };
 use frame_system::{
-    self as system, ensure_signed,
+    self as system, ensure_none, ensure_signed,
     offchain::{AppCrypto, CreateSignedTransaction, SendSignedTransaction, Signer},
 };
-use hex::FromHex;
 use sp_core::crypto::KeyTypeId;
 use sp_core::{ed25519, RuntimeDebug, H256};
 use sp_std::{prelude::*, str};
 pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"demo");
 use fixed::{types::I32F32, types::U128F0, types::U64F64};
+use pallet_organisation::Role;
 use pallet_timestamp as timestamp;
 
 /// Based on the above `KeyTypeId` we need to generate a pallet-specific crypto type wrapper.
@@ -66,6 +74,8 @@ pub mod crypto {
 
 #[cfg(test)]
 mod mock;
+#[cfg(test)]
+mod tests;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Encode, Decode, RuntimeDebug)]
 pub enum WorkloadState {
@@ -82,6 +92,8 @@ impl Default for WorkloadState {
 
 pub type BalanceOf<T> =
     <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+pub type NegativeImbalanceOf<T> =
+    <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
 
 const PALLET_ID: ModuleId = ModuleId(*b"Charity!");
 
@@ -91,6 +103,65 @@ pub struct VolumeType {
     size: u64,
 }
 
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub struct Node {
+    pubkey: ed25519::Public,
+    farm_id: u64,
+}
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct Resources {
+    cru: u64,
+    mru: u64,
+    sru: u64,
+    hru: u64,
+}
+
+impl Resources {
+    fn add(&self, other: &Resources) -> Resources {
+        Resources {
+            cru: self.cru.saturating_add(other.cru),
+            mru: self.mru.saturating_add(other.mru),
+            sru: self.sru.saturating_add(other.sru),
+            hru: self.hru.saturating_add(other.hru),
+        }
+    }
+
+    fn sub(&self, other: &Resources) -> Resources {
+        Resources {
+            cru: self.cru.saturating_sub(other.cru),
+            mru: self.mru.saturating_sub(other.mru),
+            sru: self.sru.saturating_sub(other.sru),
+            hru: self.hru.saturating_sub(other.hru),
+        }
+    }
+
+    fn fits_in(&self, other: &Resources) -> bool {
+        self.cru <= other.cru && self.mru <= other.mru && self.sru <= other.sru && self.hru <= other.hru
+    }
+
+    // Cores and GB of memory and disk, all weighing the same towards a farmer's bond
+    fn units(&self) -> u64 {
+        self.cru
+            .saturating_add(self.mru)
+            .saturating_add(self.sru)
+            .saturating_add(self.hru)
+    }
+}
+
+// Capacity a node reported, and the part of it held by contracts
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct Capacity {
+    total: Resources,
+    reserved: Resources,
+}
+
+impl Capacity {
+    fn free(&self) -> Resources {
+        self.total.sub(&self.reserved)
+    }
+}
+
 pub struct RSU {
     cru: u64,
     hru: I32F32,
@@ -99,6 +170,24 @@ pub struct RSU {
 }
 
 impl VolumeType {
+    fn is_valid(&self) -> bool {
+        self.disk_type == 1 || self.disk_type == 2
+    }
+
+    fn get_resources(&self) -> Resources {
+        match self.disk_type {
+            1 => Resources {
+                hru: self.size,
+                ..Resources::default()
+            },
+            2 => Resources {
+                sru: self.size,
+                ..Resources::default()
+            },
+            _ => unreachable!(),
+        }
+    }
+
     fn get_rsu(&self) -> RSU {
         match self.disk_type {
             1 => RSU {
@@ -125,10 +214,16 @@ pub struct Contract<T: Trait> {
     node_id: Vec<u8>,
     farmer_account: T::AccountId,
     user_account: T::AccountId,
+    user_twin_id: u64,
     accepted: bool,
     workload_state: WorkloadState,
     expires_at: u64,
     last_claimed: u64,
+    // Seconds since last_claimed during which the node was down, these are not billed
+    downtime: u64,
+    deployed_at: u64,
+    // Set while billing is paused, e.g. after a failed storage challenge
+    billing_paused_at: u64,
 }
 
 impl<T> Default for Contract<T>
@@ -146,17 +241,169 @@ where
             node_id: [0].to_vec(),
             farmer_account,
             user_account,
+            user_twin_id: 0,
             accepted: false,
             workload_state: WorkloadState::Created,
             expires_at: 0,
             last_claimed: 0,
+            downtime: 0,
+            deployed_at: 0,
+            billing_paused_at: 0,
         }
     }
 }
 
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct Dispute {
+    evidence: Vec<u8>,
+    opened_at: u64,
+    // Set when the contract ended while disputed, billing stops there
+    ended_at: Option<u64>,
+}
+
+// A range of a file the user stored on a volume, committed to as
+// blake2_256(blake2_256(bytes)) so the chain never holds the expected answer
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct ChallengeSeed {
+    offset: u64,
+    length: u64,
+    commitment: H256,
+}
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct StorageChallenge<BlockNumber> {
+    seed: ChallengeSeed,
+    deadline: BlockNumber,
+}
+
+// Outcome of a deployment a node signs, so anyone can relay it to the chain
+#[derive(PartialEq, Eq, Clone, Copy, Encode, Decode, RuntimeDebug)]
+pub enum DeploymentResult {
+    Deployed,
+    Cancelled,
+}
+
+// Calls a farmer or node can let a delegate account make on its behalf
+#[derive(PartialEq, Eq, Clone, Copy, Encode, Decode, RuntimeDebug)]
+pub enum DelegateScope {
+    AcceptContract,
+    ClaimFunds,
+    ContractDeployed,
+    ContractCancelled,
+    Heartbeat,
+}
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct Delegation<BlockNumber> {
+    scopes: Vec<DelegateScope>,
+    expires_at: BlockNumber,
+}
+
+// Contracts a farmer accepts without signing anything, per farm or per node
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct AcceptancePolicy<AccountId> {
+    max_resources: Resources,
+    // Disk types of the volumes the farmer hosts
+    allowed_disk_types: Vec<u8>,
+    // Seconds the contract has to be funded for when it is paid
+    min_prepaid_duration: u64,
+    // Prices the contract has to pay at least, the contract's own price isn't signed by the farmer
+    min_prices: ResourcePrice,
+    // When not empty, only these users are accepted
+    allowed_users: Vec<AccountId>,
+    denied_users: Vec<AccountId>,
+}
+
+impl<AccountId: PartialEq> AcceptancePolicy<AccountId> {
+    fn accepts(&self, user: &AccountId, volume: &VolumeType, prices: &ResourcePrice, prepaid_duration: u64) -> bool {
+        volume.get_resources().fits_in(&self.max_resources)
+            && self.allowed_disk_types.contains(&volume.disk_type)
+            && prices.covers(&self.min_prices)
+            && prepaid_duration >= self.min_prepaid_duration
+            && (self.allowed_users.is_empty() || self.allowed_users.contains(user))
+            && !self.denied_users.contains(user)
+    }
+}
+
+// A price the farmer proposes instead of the one the contract was priced at
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct Offer<BlockNumber> {
+    resource_prices: ResourcePrice,
+    expires_at: BlockNumber,
+}
+
+// A price a farmer signs for a contract, the user can lock it in until `valid_until` (seconds)
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct Quote {
+    reservation_id: u64,
+    resource_prices: ResourcePrice,
+    valid_until: u64,
+    // Seconds the price is locked for once the quote is accepted
+    lock_duration: u64,
+}
+
+// What happens to the contracts of a farm once their price lock ended and the farmer changes prices
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub enum PriceChangePolicy {
+    // Running contracts keep the price they were created with
+    KeepPrice,
+    // The new price applies after a notice period in seconds, users can cancel in the meantime
+    Notice(u64),
+}
+
+impl Default for PriceChangePolicy {
+    fn default() -> PriceChangePolicy {
+        PriceChangePolicy::KeepPrice
+    }
+}
+
+// Refills a contract from its user's account when its runway (seconds) drops below the threshold
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct TopUpRule<Balance> {
+    threshold: u64,
+    amount: Balance,
+    // Total the rule may ever draw from the user
+    cap: Balance,
+    spent: Balance,
+}
+
+// A new workload for a running contract, applied once the farmer accepted and the node confirmed it
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct ContractUpdate {
+    volume: VolumeType,
+    accepted: bool,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Encode, Decode, RuntimeDebug)]
+pub enum GroupState {
+    // Waiting for all its contracts to be deployed
+    Pending,
+    Active,
+    RolledBack,
+}
+
+impl Default for GroupState {
+    fn default() -> GroupState {
+        GroupState::Pending
+    }
+}
+
+// Contracts deployed together, none of them is billed until all of them run
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct DeploymentGroup<AccountId> {
+    owner: AccountId,
+    reservation_ids: Vec<u64>,
+    state: GroupState,
+}
+
+pub const MAX_GROUP_SIZE: usize = 32;
+// Seeds a volume holds at most, each one is used for a single challenge
+pub const MAX_CHALLENGE_SEEDS: usize = 64;
+
 pub const EXPLORER_NODES: &str = "https://explorer.devnet.grid.tf/explorer/nodes/";
 pub const EXPLORER_FARMS: &str = "https://explorer.devnet.grid.tf/explorer/farms/";
-pub const EXPLORER_USERS: &str = "https://explorer.devnet.grid.tf/explorer/users/";
+// DID service type whose endpoint holds the SCALE encoded `ResourcePrice` of a farm
+pub const PRICING_SERVICE: &[u8] = b"TfResourcePrice";
 pub const FETCH_TIMEOUT_PERIOD: u64 = 10000; // in milli-seconds
 pub const LOCK_TIMEOUT_EXPIRATION: u64 = FETCH_TIMEOUT_PERIOD + 1000; // in milli-seconds
 pub const LOCK_BLOCK_EXPIRATION: u32 = 3; // in block number
@@ -178,16 +425,9 @@ struct FarmInfo {
     resource_prices: Vec<ResourcePrice>,
 }
 
-struct Farm {
+struct Farm<AccountId> {
     farm_info: FarmInfo,
-    pubkey: Vec<u8>,
-}
-
-#[serde(crate = "alt_serde")]
-#[derive(Deserialize, Encode, Decode, Default)]
-struct UserInfo {
-    #[serde(deserialize_with = "de_string_to_bytes")]
-    pubkey: Vec<u8>,
+    farmer_account: AccountId,
 }
 
 #[serde(crate = "alt_serde")]
@@ -203,6 +443,18 @@ pub struct ResourcePrice {
     mru: u64,
 }
 
+impl ResourcePrice {
+    // Whether every resource costs at least as much as in `floor`
+    fn covers(&self, floor: &ResourcePrice) -> bool {
+        self.currency == floor.currency
+            && self.sru >= floor.sru
+            && self.hru >= floor.hru
+            && self.cru >= floor.cru
+            && self.nru >= floor.nru
+            && self.mru >= floor.mru
+    }
+}
+
 pub fn de_string_to_bytes<'de, D>(de: D) -> Result<Vec<u8>, D::Error>
 where
     D: Deserializer<'de>,
@@ -224,11 +476,54 @@ impl fmt::Debug for NodeInfo {
     }
 }
 
-pub trait Trait: system::Trait + CreateSignedTransaction<Call<Self>> + timestamp::Trait {
+pub trait Trait:
+    system::Trait
+    + CreateSignedTransaction<Call<Self>>
+    + timestamp::Trait
+    + pallet_twin::Trait
+    + pallet_did::Trait
+    + pallet_organisation::Trait
+{
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
-    type Currency: Currency<Self::AccountId>;
+    type Currency: ReservableCurrency<Self::AccountId>;
     type RandomnessSource: Randomness<H256>;
     type AuthorityId: AppCrypto<Self::Public, Self::Signature>;
+    // Seconds a node may stay silent before it is considered down
+    type HeartbeatInterval: Get<u64>;
+    // Bond a farmer holds for every resource unit its nodes register
+    type BondPerUnit: Get<BalanceOf<Self>>;
+    // Part of a farmer's bond slashed for every proven SLA breach
+    type SlashFraction: Get<Perbill>;
+    // Seconds a node may be down past its heartbeat interval before its farm is slashed for it
+    type OutageGracePeriod: Get<u64>;
+    // Seconds of downtime that cost a farm the whole SlashFraction, shorter outages cost a share of it
+    type SlashDowntime: Get<u64>;
+    // Origin that settles disputes between users and farmers
+    type ArbitrationOrigin: EnsureOrigin<Self::Origin>;
+    // Seconds after deployment during which a user can dispute it
+    type ChallengeWindow: Get<u64>;
+    // Blocks between storage challenges for a volume
+    type StorageChallengeInterval: Get<Self::BlockNumber>;
+    // Blocks a node has to answer a storage challenge
+    type StorageChallengeTimeout: Get<Self::BlockNumber>;
+    // Blocks between sweeps paying out farmers that opted in to automatic payouts, zero disables them
+    type AutoPayoutPeriod: Get<Self::BlockNumber>;
+    // Contracts a payout sweep processes per block at most
+    type MaxPayoutsPerBlock: Get<u32>;
+    // Contracts a single claim_all call settles at most
+    type MaxClaimsPerCall: Get<u32>;
+    // Blocks before a new payout address takes effect, so a stolen key can't redirect payouts at once
+    type PayoutChangeDelay: Get<Self::BlockNumber>;
+    // Blocks over which the fee-free reports of a node are counted
+    type FreeReportPeriod: Get<Self::BlockNumber>;
+    // Fee-free contract_deployed / contract_cancelled calls a node gets per period
+    type MaxFreeReportsPerPeriod: Get<u32>;
+    // Blocks a user has to confirm a farmer's counter-offer
+    type OfferDuration: Get<Self::BlockNumber>;
+    // Seconds the first payment of a contract has to cover, unless its farm requires more or less
+    type MinimumPrepaidDuration: Get<u64>;
+    // Funded contracts a node holds at most, so a heartbeat crediting them for an outage stays bounded
+    type MaxContractsPerNode: Get<u32>;
 }
 
 decl_storage! {
@@ -238,6 +533,62 @@ decl_storage! {
         pub VolumeReservations get(fn volume_reservations): map hasher (blake2_128_concat) u64 => VolumeType;
         pub Contracts get(fn contracts): map hasher (blake2_128_concat) u64 => Contract<T>;
         pub ContractPerExpiration get(fn contracts_per_expiration): map hasher (blake2_128_concat) u64 => Vec<u64>;
+        pub Nodes get(fn nodes): map hasher(blake2_128_concat) Vec<u8> => Option<Node>;
+        pub NodesForFarm get(fn nodes_for_farm): map hasher(blake2_128_concat) u64 => Vec<Vec<u8>>;
+        pub FarmOwner get(fn farm_owner): map hasher(blake2_128_concat) u64 => Option<T::AccountId>;
+        pub NodeNonce get(fn node_nonce): map hasher(blake2_128_concat) ed25519::Public => u64;
+        pub FarmDid get(fn farm_did): map hasher(blake2_128_concat) u64 => Option<Vec<u8>>;
+        pub NodeDid get(fn node_did): map hasher(blake2_128_concat) Vec<u8> => Option<Vec<u8>>;
+        pub NodeCapacity get(fn node_capacity): map hasher(blake2_128_concat) Vec<u8> => Capacity;
+        // Resources a contract holds on its node, from its first payment until it ends
+        pub ReservedCapacity get(fn reserved_capacity): map hasher(blake2_128_concat) u64 => Option<Resources>;
+        // Contracts funded on a node, until they end
+        pub ContractsForNode get(fn contracts_for_node): map hasher(blake2_128_concat) Vec<u8> => Vec<u64>;
+        pub LastHeartbeat get(fn last_heartbeat): map hasher(blake2_128_concat) Vec<u8> => u64;
+        pub FarmBond get(fn farm_bond): map hasher(blake2_128_concat) u64 => BalanceOf<T>;
+        pub Disputes get(fn disputes): map hasher(blake2_128_concat) u64 => Option<Dispute>;
+        pub ChallengeSeeds get(fn challenge_seeds): map hasher(blake2_128_concat) u64 => Vec<ChallengeSeed>;
+        pub OpenChallenges get(fn open_challenges): map hasher(blake2_128_concat) u64 => Option<StorageChallenge<T::BlockNumber>>;
+        pub ChallengesAt get(fn challenges_at): map hasher(blake2_128_concat) T::BlockNumber => Vec<u64>;
+        pub ChallengeDeadlines get(fn challenge_deadlines): map hasher(blake2_128_concat) T::BlockNumber => Vec<u64>;
+        // Block the next challenge of a volume is issued at, a volume has one scheduled at most
+        pub ChallengeScheduledAt get(fn challenge_scheduled_at): map hasher(blake2_128_concat) u64 => Option<T::BlockNumber>;
+        pub AutoPayout get(fn auto_payout): map hasher(blake2_128_concat) T::AccountId => bool;
+        pub ContractsForFarmer get(fn contracts_for_farmer): map hasher(blake2_128_concat) T::AccountId => Vec<u64>;
+        pub PayoutAddress get(fn payout_address): map hasher(blake2_128_concat) T::AccountId => Option<T::AccountId>;
+        pub PendingPayoutAddress get(fn pending_payout_address): map hasher(blake2_128_concat) T::AccountId => Option<(T::AccountId, T::BlockNumber)>;
+        pub Delegates get(fn delegates): double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) T::AccountId => Option<Delegation<T::BlockNumber>>;
+        pub FarmAcceptancePolicy get(fn farm_acceptance_policy): map hasher(blake2_128_concat) u64 => Option<AcceptancePolicy<T::AccountId>>;
+        pub NodeAcceptancePolicy get(fn node_acceptance_policy): map hasher(blake2_128_concat) Vec<u8> => Option<AcceptancePolicy<T::AccountId>>;
+        pub Offers get(fn offers): map hasher(blake2_128_concat) u64 => Option<Offer<T::BlockNumber>>;
+        pub OffersExpiringAt get(fn offers_expiring_at): map hasher(blake2_128_concat) T::BlockNumber => Vec<u64>;
+        // Seconds until which a contract keeps the price of the quote it accepted
+        pub PriceLocks get(fn price_locks): map hasher(blake2_128_concat) u64 => u64;
+        pub FarmMinimumPrepaidDuration get(fn farm_minimum_prepaid_duration): map hasher(blake2_128_concat) u64 => Option<u64>;
+        pub FarmPriceChangePolicy get(fn farm_price_change_policy): map hasher(blake2_128_concat) u64 => PriceChangePolicy;
+        // New price of a contract and the second it applies from
+        pub PendingPriceChanges get(fn pending_price_changes): map hasher(blake2_128_concat) u64 => Option<(ResourcePrice, u64)>;
+        pub TopUpRules get(fn top_up_rules): map hasher(blake2_128_concat) u64 => Option<TopUpRule<BalanceOf<T>>>;
+        // Timestamps (seconds) at which the runway of contracts with a top-up rule is checked
+        pub TopUpChecks get(fn top_up_checks): map hasher(blake2_128_concat) u64 => Vec<u64>;
+        // What each payer put into a contract, less what it took back
+        pub Contributions get(fn contributions): double_map hasher(blake2_128_concat) u64, hasher(blake2_128_concat) T::AccountId => BalanceOf<T>;
+        // Accounts the user lets fund a contract, up to a cap
+        pub Sponsors get(fn sponsors): double_map hasher(blake2_128_concat) u64, hasher(blake2_128_concat) T::AccountId => Option<BalanceOf<T>>;
+        // Organisation owning a contract, its account is the contract's user
+        pub ContractOrg get(fn contract_org): map hasher(blake2_128_concat) u64 => Option<u64>;
+        pub ReservationsForOrg get(fn reservations_for_org): map hasher(blake2_128_concat) u64 => Vec<u64>;
+        pub PendingUpdates get(fn pending_updates): map hasher(blake2_128_concat) u64 => Option<ContractUpdate>;
+        pub Groups get(fn groups): map hasher(blake2_128_concat) u64 => Option<DeploymentGroup<T::AccountId>>;
+        pub GroupOf get(fn group_of): map hasher(blake2_128_concat) u64 => Option<u64>;
+        // Account a contract's user offered the contract to
+        pub PendingTransfers get(fn pending_transfers): map hasher(blake2_128_concat) u64 => Option<T::AccountId>;
+        // Start of the current period and the fee-free reports a node made in it
+        pub FreeReports get(fn free_reports): map hasher(blake2_128_concat) T::AccountId => (T::BlockNumber, u32);
+        // Next reservation ID of a payout sweep that is in progress
+        PayoutCursor: Option<u64>;
+        FarmID: u64;
+        GroupID: u64;
         ReservationID: u64;
         LastBlockTime: u64;
     }
@@ -247,6 +598,8 @@ decl_event!(
     pub enum Event<T>
     where
         AccountId = <T as frame_system::Trait>::AccountId,
+        Balance = BalanceOf<T>,
+        BlockNumber = <T as frame_system::Trait>::BlockNumber,
     {
         // Will signal a contract has been added for a specific users, for a specific nodeID with a reservationID
         ContractAdded(AccountId, Vec<u8>, u64),
@@ -257,6 +610,81 @@ decl_event!(
         // Will signal a contract being accepted for a NodeID and a reservation ID
         ContractAccepted(Vec<u8>, u64),
         ContractFundsClaimed(u64),
+        // Will signal a node being registered for a farm
+        NodeRegistered(Vec<u8>, u64),
+        FarmDidSet(u64, Vec<u8>),
+        NodeDidSet(Vec<u8>, Vec<u8>),
+        NodeCapacityReported(Vec<u8>, Resources),
+        NodeHeartbeat(Vec<u8>, u64),
+        // Will signal a node was down from a timestamp until another one
+        NodeOutage(Vec<u8>, u64, u64),
+        FarmBonded(u64, Balance),
+        FarmUnbonded(u64, Balance),
+        // Will signal part of a farm's bond being paid to a user it failed
+        FarmerSlashed(u64, AccountId, Balance),
+        DisputeOpened(AccountId, u64),
+        // Will signal a dispute being resolved with the amounts refunded to the user and paid to the farmer
+        DisputeResolved(u64, Balance, Balance),
+        // Will signal a node to answer with the hash of a range (offset, length) of a volume
+        StorageChallengeIssued(Vec<u8>, u64, u64, u64),
+        StorageChallengePassed(u64),
+        StorageChallengeFailed(u64),
+        StorageChallengeMissed(u64),
+        BillingPaused(u64),
+        BillingResumed(u64),
+        AutoPayoutSet(AccountId, bool),
+        // Will signal the amount a single contract contributed to a claim_all
+        ContractFundsSettled(u64, Balance),
+        // Will signal the total claimed by a claim_all, and the reservation id to continue after if any
+        AllFundsClaimed(AccountId, Balance, Option<u64>),
+        // Will signal a farmer's new payout address and the block it takes effect at
+        PayoutAddressChangeScheduled(AccountId, AccountId, BlockNumber),
+        PayoutAddressChangeCancelled(AccountId),
+        // Will signal an account (farmer or node) letting a delegate act for it until a block
+        DelegateAdded(AccountId, AccountId, BlockNumber),
+        DelegateRemoved(AccountId, AccountId),
+        FarmAcceptancePolicySet(u64),
+        NodeAcceptancePolicySet(Vec<u8>),
+        // Will signal a farmer proposing a price for a contract, valid until a block
+        CounterOffered(u64, ResourcePrice, BlockNumber),
+        OfferConfirmed(u64),
+        OfferRejected(u64),
+        OfferExpired(u64),
+        // Will signal a user locking in a farmer's quoted price until a timestamp
+        QuoteAccepted(u64, u64),
+        PriceChangePolicySet(u64, PriceChangePolicy),
+        // Will signal the new price of a contract and the timestamp it applies from
+        PriceChangeAnnounced(u64, ResourcePrice, u64),
+        PriceChanged(u64),
+        MinimumPrepaidDurationSet(u64, Option<u64>),
+        // Will signal a user taking back part of a contract's prepaid funds
+        FundsWithdrawn(AccountId, u64, Balance),
+        AutoTopUpSet(u64),
+        AutoTopUpRemoved(u64),
+        ContractToppedUp(u64, Balance),
+        // Will signal a top-up the user's free balance could not cover
+        TopUpFailed(u64, Balance),
+        TopUpCapReached(u64),
+        SponsorAdded(u64, AccountId, Balance),
+        SponsorRemoved(u64, AccountId),
+        ContributionRefunded(u64, AccountId, Balance),
+        ContractTransferOffered(u64, AccountId),
+        ContractTransferCancelled(u64),
+        // Will signal a contract moving from its previous owner to a new one
+        ContractTransferred(u64, AccountId, AccountId),
+        ContractUpdateRequested(Vec<u8>, u64),
+        // Will signal a node to resize the workload of a contract to the accepted volume
+        ContractUpdateAccepted(Vec<u8>, u64, VolumeType),
+        ContractUpdateCancelled(u64),
+        ContractResized(Vec<u8>, u64),
+        ContractRejected(Vec<u8>, u64),
+        // Will signal a deployment group being created with its contracts
+        GroupCreated(AccountId, u64, Vec<u64>),
+        GroupFunded(u64, Balance),
+        GroupActivated(u64),
+        GroupRolledBack(u64),
+        FarmCreated(u64, AccountId),
+        ContractClaimSkipped(u64),
     }
 );
 
@@ -279,6 +707,53 @@ decl_error! {
         UnauthorizedNode,
         NotEnoughBalanceToClaim,
         ClaimError,
+        NodeNotExists,
+        InvalidNodeSignature,
+        TwinNotExists,
+        DidNotExists,
+        UnauthorizedDid,
+        DidKeyMismatch,
+        InvalidVolume,
+        NodeCapacityExceeded,
+        CapacityBelowReserved,
+        ContractCancelled,
+        InsufficientBond,
+        ChallengeWindowClosed,
+        ContractDisputed,
+        DisputeExists,
+        DisputeNotExists,
+        ChallengeNotExists,
+        NoPendingPayoutAddress,
+        DelegateNotExists,
+        DelegationExpired,
+        ContractAlreadyDeployed,
+        OfferPending,
+        OfferNotExists,
+        OfferExpired,
+        QuoteExpired,
+        InvalidQuoteSignature,
+        ContractNotPriced,
+        InsufficientPrepayment,
+        InsufficientContractBalance,
+        TopUpRuleNotExists,
+        NotASponsor,
+        SponsorCapExceeded,
+        NotAnOrgContract,
+        TransferNotExists,
+        UpdatePending,
+        UpdateNotExists,
+        UpdateNotAccepted,
+        GroupNotExists,
+        InvalidGroupSize,
+        GroupNotPending,
+        InsufficientBalance,
+        GroupNotActive,
+        ContractAlreadyFunded,
+        FarmNotExists,
+        NodeContractLimitReached,
+        TooManyChallengeSeeds,
+        PayoutBelowExistentialDeposit,
+        OrgContractPayment,
     }
 }
 
@@ -288,298 +763,1276 @@ decl_module! {
 
         fn deposit_event() = default;
 
-        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
-        pub fn create_contract(origin, node_id: Vec<u8>, volume: VolumeType) -> DispatchResult {
+        // The farm is owned by the account that creates it, nodes are registered for it by that account only.
+        // The owner needs a twin, its farmers get paid on the twin's account
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,2)]
+        pub fn create_farm(origin) -> DispatchResult {
             let who = ensure_signed(origin)?;
+            ensure!(pallet_twin::Module::<T>::twin_of(&who).is_some(), Error::<T>::TwinNotExists);
 
-            let reservation_id = ReservationID::get();
+            let farm_id = FarmID::get();
+            debug::info!("Creating farm: {:?} for: {:?}", farm_id, who);
 
-            ensure!(!Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractExists);
+            FarmOwner::<T>::insert(farm_id, &who);
+            FarmID::put(farm_id + 1);
 
-            // Create a contract
-            let mut contract = Contract::default();
+            Self::deposit_event(RawEvent::FarmCreated(farm_id, who));
 
-            contract.node_id = node_id.clone();
-            debug::info!("Contract with id: {:?} and nodeId: {:?}", reservation_id, contract.node_id);
+            Ok(())
+        }
 
-            // Create a new accountID based on the reservationID and assign it to the contract
-            let account_id = PALLET_ID.into_sub_account(reservation_id);
-            let _ = T::Currency::make_free_balance_be(
-                &account_id,
-                T::Currency::minimum_balance(),
-            );
-            debug::info!("Assigned accountID: {:?} to contract with id: {:?}", account_id, reservation_id);
-            contract.account_id = account_id;
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(4,3)]
+        pub fn register_node(origin, farm_id: u64, pubkey: ed25519::Public, signature: ed25519::Signature) -> DispatchResult {
+            let who = ensure_signed(origin)?;
 
-            // Add the user account to the contract
-            contract.user_account = who.clone();
+            let owner = FarmOwner::<T>::get(farm_id).ok_or(Error::<T>::FarmNotExists)?;
+            ensure!(owner == who, Error::<T>::UnauthorizedFarmer);
 
-            // Update the contract
-            Contracts::<T>::insert(&reservation_id, &contract);
+            // The node proves ownership of its key by signing the farm id, the registering account and its nonce
+            let nonce = NodeNonce::get(&pubkey);
+            let payload = (farm_id, &who, nonce).encode();
+            ensure!(sp_io::crypto::ed25519_verify(&signature, &payload, &pubkey), Error::<T>::InvalidNodeSignature);
+            NodeNonce::insert(&pubkey, nonce + 1);
 
-            // TODO, make generic for each workload type
-            VolumeReservations::insert(reservation_id, &volume);
-            ReservationID::put(reservation_id + 1);
+            let node_id = bs58::encode(&pubkey[..]).into_vec();
+            debug::info!("Registering node: {:?} for farm: {:?}", node_id, farm_id);
+
+            // A node that re-registers is moved from its previous farm
+            if let Some(node) = Nodes::get(&node_id) {
+                NodesForFarm::mutate(node.farm_id, |list| list.retain(|id| id != &node_id));
+            }
 
-            ReservationsForAccount::<T>::mutate(&who, |list|  list.push(reservation_id));
+            Nodes::insert(&node_id, Node { pubkey, farm_id });
+            NodesForFarm::mutate(farm_id, |list| list.push(node_id.clone()));
 
-            Self::deposit_event(RawEvent::ContractAdded(who, node_id, reservation_id));
+            Self::deposit_event(RawEvent::NodeRegistered(node_id, farm_id));
 
             Ok(())
         }
 
-        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
-        pub fn pay(origin, reservation_id: u64, amount: BalanceOf<T>) -> DispatchResult {
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn set_farm_did(origin, farm_id: u64, did: Vec<u8>) -> DispatchResult {
             let who = ensure_signed(origin)?;
 
-            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+            ensure!(FarmOwner::<T>::get(farm_id) == Some(who.clone()), Error::<T>::UnauthorizedFarmer);
 
-            let mut contract = Contracts::<T>::get(reservation_id);
+            let document = pallet_did::Module::<T>::resolve_active(&did).ok_or(Error::<T>::DidNotExists)?;
+            ensure!(document.controller == who, Error::<T>::UnauthorizedDid);
 
-            // ensure!(contract.accepted == true, Error::<T>::ContractNotAccepted);
+            FarmDid::insert(farm_id, &did);
 
-            debug::info!("Transfering: {:?} from {:?} to contract accountId: {:?}", &amount, &who, &contract.account_id);
-            // Transfer currency to the contracts account
-            T::Currency::transfer(&who, &contract.account_id, amount, AllowDeath)
-                .map_err(|_| DispatchError::Other("Can't make transfer"))?;
+            Self::deposit_event(RawEvent::FarmDidSet(farm_id, did));
 
-            // Reevauluate contract expiration date
-            // check if the expiration date is set first in order to not confuse the user
-            let volume = VolumeReservations::get(reservation_id);
+            Ok(())
+        }
 
-            let sru = volume.get_rsu();
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(3,1)]
+        pub fn set_node_did(origin, node_id: Vec<u8>, did: Vec<u8>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
 
-            let price_per_hour = Self::get_price_per_hour(&contract.resource_prices, sru);
-            let price_per_sec = U64F64::from_num(price_per_hour) * U64F64::from_num(1e12) / U64F64::from_num(3600);
+            let node = Nodes::get(&node_id).ok_or(Error::<T>::NodeNotExists)?;
+            ensure!(FarmOwner::<T>::get(node.farm_id) == Some(who.clone()), Error::<T>::UnauthorizedFarmer);
 
-            // Get the contract's balance
-            let balance: BalanceOf<T> = T::Currency::free_balance(&contract.account_id);
-            let balances_as_u128: u128 = balance.saturated_into::<u128>();
+            let document = pallet_did::Module::<T>::resolve_active(&did).ok_or(Error::<T>::DidNotExists)?;
+            ensure!(document.controller == who, Error::<T>::UnauthorizedDid);
 
-            let expires_at = (U128F0::from_num(balances_as_u128) / U128F0::from_num(price_per_sec)).to_num::<u64>();
+            // The DID has to identify the node by the same key it registered with
+            ensure!(Self::did_node_key(&did) == Some(node.pubkey), Error::<T>::DidKeyMismatch);
 
-            // Update expires_at if there is an expiration date, this means the user is probably re-funding the contract
-            if contract.expires_at > 0 {
-                // Since the contract is expiration date will be updated we need to remove it from the list first
-                // in order to prevent it from getting cancelled before the new expiration date
-                ContractPerExpiration::mutate(&contract.expires_at, |list|  {
-                    debug::info!("list: {:?}", list);
-                    if list.len() > 0 {
-                        let index = list.iter().position(|x| x == &reservation_id).unwrap();
-                        list.remove(index);
-                    }
-                });
+            NodeDid::insert(&node_id, &did);
 
-                contract.expires_at += expires_at;
-                debug::info!("Reevauluating contract expiration, expires at: {:?}", &contract.expires_at);
-            } else {
-                let now = <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000;
-                contract.expires_at = now + expires_at;
-                debug::info!("Contract will expire at: {:?}", &contract.expires_at);
-            }
+            Self::deposit_event(RawEvent::NodeDidSet(node_id, did));
 
-            // Update the contract
-            Contracts::<T>::insert(&reservation_id, &contract);
-            // Insert the reservationID at contract expiration date
-            ContractPerExpiration::mutate(&contract.expires_at, |list|  list.push(reservation_id));
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn report_capacity(origin, node_id: Vec<u8>, total: Resources) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // Ensure the node signed
+            ensure!(Self::node_account(&node_id)? == who, Error::<T>::UnauthorizedNode);
+
+            let mut capacity = NodeCapacity::get(&node_id);
+            // A node can't shrink below what its contracts already hold
+            ensure!(capacity.reserved.fits_in(&total), Error::<T>::CapacityBelowReserved);
+
+            // The farm's bond has to cover the capacity of all its nodes
+            let farm_id = Nodes::get(&node_id).ok_or(Error::<T>::NodeNotExists)?.farm_id;
+            let units = Self::farm_units(farm_id)
+                .saturating_sub(capacity.total.units())
+                .saturating_add(total.units());
+            ensure!(FarmBond::<T>::get(farm_id) >= Self::bond_for(units), Error::<T>::InsufficientBond);
+
+            capacity.total = total.clone();
+            NodeCapacity::insert(&node_id, &capacity);
 
-            Self::deposit_event(RawEvent::ContractPaid(contract.account_id, reservation_id));
+            Self::deposit_event(RawEvent::NodeCapacityReported(node_id, total));
 
             Ok(())
         }
 
-        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
-        pub fn set_contract_price(origin, reservation_id: u64, resource_prices: ResourcePrice, farmer_account: <T as frame_system::Trait>::AccountId) -> DispatchResult {
-            // TODO: Only off chain worker can sign this
-            let _ = ensure_signed(origin)?;
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,2)]
+        pub fn bond(origin, farm_id: u64, amount: BalanceOf<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
 
-            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+            ensure!(FarmOwner::<T>::get(farm_id) == Some(who.clone()), Error::<T>::UnauthorizedFarmer);
 
-            let mut contract = Contracts::<T>::get(reservation_id);
+            <T as Trait>::Currency::reserve(&who, amount)?;
+            FarmBond::<T>::mutate(farm_id, |bond| *bond = bond.saturating_add(amount));
 
-            contract.resource_prices = resource_prices;
-            contract.farmer_account = farmer_account;
+            Self::deposit_event(RawEvent::FarmBonded(farm_id, amount));
 
-            // Update the contract
-            Contracts::<T>::insert(&reservation_id, &contract);
+            Ok(())
+        }
 
-            Self::deposit_event(RawEvent::ContractUpdated(contract.account_id, reservation_id));
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,2)]
+        pub fn unbond(origin, farm_id: u64, amount: BalanceOf<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(FarmOwner::<T>::get(farm_id) == Some(who.clone()), Error::<T>::UnauthorizedFarmer);
+
+            // What stays bonded must still cover the farm's registered capacity
+            let bond = FarmBond::<T>::get(farm_id);
+            ensure!(bond >= amount, Error::<T>::InsufficientBond);
+            let remaining = bond - amount;
+            ensure!(remaining >= Self::bond_for(Self::farm_units(farm_id)), Error::<T>::InsufficientBond);
+
+            <T as Trait>::Currency::unreserve(&who, amount);
+            FarmBond::<T>::insert(farm_id, remaining);
+
+            Self::deposit_event(RawEvent::FarmUnbonded(farm_id, amount));
 
             Ok(())
         }
 
-        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
-        pub fn accept_contract(origin, reservation_id: u64) -> DispatchResult {
+        // A heartbeat after an outage goes through all contracts of the node, and slashes the farm to their users
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(
+            6 + 4 * T::MaxContractsPerNode::get() as Weight,
+            2 + 3 * T::MaxContractsPerNode::get() as Weight,
+        )]
+        pub fn heartbeat(origin, node_id: Vec<u8>) -> DispatchResultWithPostInfo {
             let who = ensure_signed(origin)?;
-            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
 
-            let mut contract = Contracts::<T>::get(reservation_id);
+            // Ensure the node signed
+            ensure!(Self::is_authorized(&Self::node_account(&node_id)?, &who, DelegateScope::Heartbeat), Error::<T>::UnauthorizedNode);
 
-            // Ensure only the farmer of the contract can accept the contract
-            ensure!(contract.farmer_account == who, Error::<T>::UnauthorizedFarmer);
+            let now = <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000;
+            let last_heartbeat = LastHeartbeat::get(&node_id);
 
-            contract.accepted = true;
+            let processed = if now > last_heartbeat + T::HeartbeatInterval::get() {
+                Self::note_outage(&node_id, last_heartbeat, now)
+            } else {
+                0
+            };
 
-            // Update the contract
-            Contracts::<T>::insert(&reservation_id, &contract);
+            LastHeartbeat::insert(&node_id, now);
 
-            Self::deposit_event(RawEvent::ContractAccepted(contract.node_id, reservation_id));
+            Self::deposit_event(RawEvent::NodeHeartbeat(node_id, now));
+
+            Ok(Some(10_000 + T::DbWeight::get().reads_writes(6 + 4 * processed, 2 + 3 * processed)).into())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn create_contract(origin, node_id: Vec<u8>, volume: VolumeType) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            Self::do_create_contract(&who, who.clone(), node_id, volume)?;
 
             Ok(())
         }
 
-        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
-        pub fn claim_funds(origin, reservation_id: u64) -> DispatchResult {
+        // Refuse a contract that was not deployed yet, the user gets its funds back
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(4,5)]
+        pub fn reject_contract(origin, reservation_id: u64) -> DispatchResult {
             let who = ensure_signed(origin)?;
             ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
 
             let mut contract = Contracts::<T>::get(reservation_id);
 
-            ensure!(contract.workload_state == WorkloadState::Deployed, Error::<T>::ContractNotDeployed);
+            ensure!(Self::is_authorized(&contract.farmer_account, &who, DelegateScope::AcceptContract), Error::<T>::UnauthorizedFarmer);
+            ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled);
+            ensure!(contract.workload_state == WorkloadState::Created, Error::<T>::ContractAlreadyDeployed);
 
-            // Ensure only the farmer of the contract can claim the funds
-            ensure!(contract.farmer_account == who, Error::<T>::UnauthorizedFarmer);
+            // A group can't run without any of its contracts
+            if let Some(group_id) = Self::pending_group(reservation_id) {
+                Self::rollback_group(group_id);
+            } else {
+                Self::refund_payers(reservation_id, &contract)?;
+                Self::end_contract(reservation_id, &mut contract);
+                Contracts::<T>::insert(&reservation_id, &contract);
+            }
 
-            // Get the contract's balance
-            let mut balance: BalanceOf<T> = T::Currency::free_balance(&contract.account_id);
+            Self::deposit_event(RawEvent::ContractRejected(contract.node_id, reservation_id));
 
-            // Evaluate if the farmer can claim funds, if yes, calculate how much
-            let volume = VolumeReservations::get(reservation_id);
+            Ok(())
+        }
 
-            let sru = volume.get_rsu();
+        // Create the contracts of a deployment, each on its own node
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(
+            2 + 4 * MAX_GROUP_SIZE as Weight,
+            2 + 6 * MAX_GROUP_SIZE as Weight,
+        )]
+        pub fn create_group(origin, workloads: Vec<(Vec<u8>, VolumeType)>) -> DispatchResultWithPostInfo {
+            let who = ensure_signed(origin)?;
 
-            let price_per_hour = Self::get_price_per_hour(&contract.resource_prices, sru);
-            let price_per_sec = U64F64::from_num(price_per_hour) * U64F64::from_num(1e12) / U64F64::from_num(3600);
+            ensure!(!workloads.is_empty() && workloads.len() <= MAX_GROUP_SIZE, Error::<T>::InvalidGroupSize);
+            ensure!(pallet_twin::Module::<T>::twin_of(&who).is_some(), Error::<T>::TwinNotExists);
 
-            let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
+            // All contracts have to be valid before any is created, capacity is only held once they are funded
+            for (node_id, volume) in &workloads {
+                ensure!(Nodes::contains_key(node_id), Error::<T>::NodeNotExists);
+                ensure!(volume.is_valid(), Error::<T>::InvalidVolume);
+            }
 
-            // convert to seconds
-            let diff = (now - contract.last_claimed) / 1000;
-            ensure!(diff > 0, Error::<T>::ClaimError);
+            let group_id = GroupID::get();
+            let mut reservation_ids = Vec::new();
+            for (node_id, volume) in workloads {
+                let reservation_id = Self::do_create_contract(&who, who.clone(), node_id, volume)?;
+                GroupOf::insert(reservation_id, group_id);
+                reservation_ids.push(reservation_id);
+            }
 
-            debug::info!("{:?} seconds have passed since last claimed, price per sec: {:?}", diff, price_per_sec);
+            Groups::<T>::insert(group_id, DeploymentGroup {
+                owner: who.clone(),
+                reservation_ids: reservation_ids.clone(),
+                state: GroupState::Pending,
+            });
+            GroupID::put(group_id + 1);
 
-            let amount_to_claim = (U64F64::from_num(diff) * price_per_sec).to_num::<u128>();
-            let balance_as_u128 = balance.saturated_into::<u128>();
+            let created = reservation_ids.len() as Weight;
+            Self::deposit_event(RawEvent::GroupCreated(who, group_id, reservation_ids));
 
-            debug::info!("Trying to claim {:?}, from contract with balance: {:?}", &amount_to_claim, &balance_as_u128);
+            Ok(Some(10_000 + T::DbWeight::get().reads_writes(2 + 4 * created, 2 + 6 * created)).into())
+        }
+
+        // Fund all contracts of a group at once, split by their price so they run out together
+        // A payment failing half way leaves none of the group funded
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(
+            1 + 6 * MAX_GROUP_SIZE as Weight,
+            4 * MAX_GROUP_SIZE as Weight,
+        )]
+        #[transactional]
+        pub fn fund_group(origin, group_id: u64, amount: BalanceOf<T>) -> DispatchResultWithPostInfo {
+            let who = ensure_signed(origin)?;
 
-            if amount_to_claim <= balance_as_u128 {
-                balance = amount_to_claim.saturated_into();
+            let group = Groups::<T>::get(group_id).ok_or(Error::<T>::GroupNotExists)?;
+            ensure!(group.state != GroupState::RolledBack, Error::<T>::GroupNotPending);
+            ensure!(<T as Trait>::Currency::free_balance(&who) >= amount, Error::<T>::InsufficientBalance);
+
+            let prices: Vec<u128> = group
+                .reservation_ids
+                .iter()
+                .map(|id| Self::price_per_sec(*id, &Contracts::<T>::get(id)).to_num::<u128>())
+                .collect();
+            let total_price = prices.iter().fold(0u128, |total, price| total.saturating_add(*price));
+
+            let mut shares = Vec::new();
+            let mut remaining = amount;
+            for (i, reservation_id) in group.reservation_ids.iter().enumerate() {
+                let share = if i + 1 == prices.len() {
+                    remaining
+                } else {
+                    Perbill::from_rational_approximation(prices[i], total_price) * amount
+                };
+                remaining = remaining.saturating_sub(share);
+
+                // Every payment has to go through before any is made
+                Self::ensure_can_pay(&who, *reservation_id, &Contracts::<T>::get(reservation_id), share)?;
+                shares.push((*reservation_id, share));
             }
 
-            debug::info!("Transfering: {:?} from contract {:?} to farmer {:?}", &balance, &contract.account_id, &who);
-            // Transfer currency to the farmers account
-            T::Currency::transfer(&contract.account_id, &contract.farmer_account, balance, AllowDeath)
-                .map_err(|_| DispatchError::Other("Can't make transfer"))?;
+            for (reservation_id, share) in shares {
+                Self::do_pay(who.clone(), reservation_id, share)?;
+            }
 
-            contract.last_claimed = now;
+            Self::deposit_event(RawEvent::GroupFunded(group_id, amount));
 
-            // Update the contract
-            Contracts::<T>::insert(&reservation_id, &contract);
+            let funded = group.reservation_ids.len() as Weight;
+            Ok(Some(10_000 + T::DbWeight::get().reads_writes(1 + 6 * funded, 4 * funded)).into())
+        }
 
-            Self::deposit_event(RawEvent::ContractFundsClaimed(reservation_id));
+        // Ask for the workload of a deployed contract to be replaced, e.g. to grow a volume without losing data
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(5,1)]
+        pub fn update_contract(origin, reservation_id: u64, volume: VolumeType) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state == WorkloadState::Deployed, Error::<T>::ContractNotDeployed);
+            ensure!(!PendingUpdates::contains_key(reservation_id), Error::<T>::UpdatePending);
+            ensure!(volume.is_valid(), Error::<T>::InvalidVolume);
+
+            let accepted = Self::policy_accepts(&contract, &volume);
+            PendingUpdates::insert(reservation_id, ContractUpdate { volume: volume.clone(), accepted });
+
+            Self::deposit_event(RawEvent::ContractUpdateRequested(contract.node_id.clone(), reservation_id));
+            if accepted {
+                Self::deposit_event(RawEvent::ContractUpdateAccepted(contract.node_id, reservation_id, volume));
+            }
 
             Ok(())
         }
 
-        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
-        pub fn cancel_contract(origin, reservation_id: u64) -> DispatchResult {
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn accept_contract_update(origin, reservation_id: u64) -> DispatchResult {
             let who = ensure_signed(origin)?;
             ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
 
             let contract = Contracts::<T>::get(reservation_id);
 
-            // Ensure only the farmer of the contract can accept the contract
-            ensure!(contract.user_account == who, Error::<T>::UnauthorizedUser);
+            ensure!(Self::is_authorized(&contract.farmer_account, &who, DelegateScope::AcceptContract), Error::<T>::UnauthorizedFarmer);
 
-            // Get the contract's balance
-            let balance: BalanceOf<T> = T::Currency::free_balance(&contract.account_id);
+            let mut update = PendingUpdates::get(reservation_id).ok_or(Error::<T>::UpdateNotExists)?;
+            update.accepted = true;
+            PendingUpdates::insert(reservation_id, &update);
 
-            debug::info!("Transfering: {:?} from contract {:?} to user {:?}", &balance, &contract.account_id, &who);
-            // Transfer currency to the users account
-            T::Currency::transfer(&contract.account_id, &contract.user_account, balance, AllowDeath)
-                .map_err(|_| DispatchError::Other("Can't make transfer"))?;
+            Self::deposit_event(RawEvent::ContractUpdateAccepted(contract.node_id, reservation_id, update.volume));
 
-            Self::deposit_event(RawEvent::ContractCancelled(contract.node_id, reservation_id));
+            Ok(())
+        }
+
+        // Withdraw an update as the user, or reject it as the farmer
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn cancel_contract_update(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            if !Self::is_authorized(&contract.farmer_account, &who, DelegateScope::AcceptContract) {
+                Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            }
+            ensure!(PendingUpdates::contains_key(reservation_id), Error::<T>::UpdateNotExists);
+
+            PendingUpdates::remove(reservation_id);
+
+            Self::deposit_event(RawEvent::ContractUpdateCancelled(reservation_id));
 
             Ok(())
         }
 
-        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
-        pub fn contract_cancelled(origin, reservation_id: u64) -> DispatchResult {
+        // The node resized the workload, billing switches to the new one from here
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(6,6)]
+        pub fn contract_updated(origin, reservation_id: u64) -> DispatchResult {
             let who = ensure_signed(origin)?;
             ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
 
             let mut contract = Contracts::<T>::get(reservation_id);
 
             // Ensure the node signed
-            let mut decoded = [0u8;32];
-            let _ = bs58::decode(&contract.node_id).into(&mut decoded).unwrap();
+            ensure!(Self::is_authorized(&Self::node_account(&contract.node_id)?, &who, DelegateScope::ContractDeployed), Error::<T>::UnauthorizedNode);
+            ensure!(contract.workload_state == WorkloadState::Deployed, Error::<T>::ContractNotDeployed);
+            ensure!(!Disputes::contains_key(reservation_id), Error::<T>::ContractDisputed);
+
+            let update = PendingUpdates::get(reservation_id).ok_or(Error::<T>::UpdateNotExists)?;
+            ensure!(update.accepted, Error::<T>::UpdateNotAccepted);
+
+            let previous = ReservedCapacity::get(reservation_id).unwrap_or_default();
+            ensure!(
+                update.volume.get_resources().fits_in(&NodeCapacity::get(&contract.node_id).free().add(&previous)),
+                Error::<T>::NodeCapacityExceeded
+            );
 
-            let node_address = ed25519::Public::from_raw(decoded);
-            ensure!(T::AccountId::decode(&mut &node_address[..]).unwrap_or_default() == who, Error::<T>::UnauthorizedNode);
+            // The farmer is paid for the previous workload up to the switch
+            Self::settle_contract(reservation_id, &mut contract)?;
+            Self::resize_capacity(reservation_id, &contract.node_id, &previous, &update.volume.get_resources())?;
 
-            contract.workload_state = WorkloadState::Cancelled;
+            VolumeReservations::insert(reservation_id, &update.volume);
+            PendingUpdates::remove(reservation_id);
+
+            let now = <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000;
+            Self::reschedule_expiration(reservation_id, &mut contract, now);
 
             // Update the contract
             Contracts::<T>::insert(&reservation_id, &contract);
 
-            Self::deposit_event(RawEvent::ContractUpdated(contract.account_id, reservation_id));
+            Self::deposit_event(RawEvent::ContractResized(contract.node_id, reservation_id));
 
             Ok(())
         }
 
-        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
-        pub fn contract_deployed(origin, reservation_id: u64) -> DispatchResult {
+        // Offer the contract to another account, which becomes its user once it accepts
+        // An organisation's contract leaves the organisation this way, so only its admins offer it
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn offer_contract_transfer(origin, reservation_id: u64, new_owner: T::AccountId) -> DispatchResult {
             let who = ensure_signed(origin)?;
             ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
 
-            let mut contract = Contracts::<T>::get(reservation_id);
+            let contract = Contracts::<T>::get(reservation_id);
 
-            // Ensure the node signed
-            let mut decoded = [0u8;32];
-            let _ = bs58::decode(&contract.node_id).into(&mut decoded).unwrap();
+            Self::ensure_contract_owner(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled);
 
-            let node_address = ed25519::Public::from_raw(decoded);
-            ensure!(T::AccountId::decode(&mut &node_address[..]).unwrap_or_default() == who, Error::<T>::UnauthorizedNode);
+            PendingTransfers::<T>::insert(reservation_id, &new_owner);
 
-            contract.workload_state = WorkloadState::Deployed;
+            Self::deposit_event(RawEvent::ContractTransferOffered(reservation_id, new_owner));
 
-            let volume = VolumeReservations::get(reservation_id);
+            Ok(())
+        }
 
-            let sru = volume.get_rsu();
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn cancel_contract_transfer(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
 
-            let price_per_hour = Self::get_price_per_hour(&contract.resource_prices, sru);
-            let price_per_sec = U64F64::from_num(price_per_hour) * U64F64::from_num(1e12) / U64F64::from_num(3600);
+            let contract = Contracts::<T>::get(reservation_id);
 
-            // Get the contract's balance
-            let balance: BalanceOf<T> = T::Currency::free_balance(&contract.account_id);
-            let balances_as_u128: u128 = balance.saturated_into::<u128>();
+            Self::ensure_contract_owner(reservation_id, &contract, &who)?;
+            ensure!(PendingTransfers::<T>::contains_key(reservation_id), Error::<T>::TransferNotExists);
 
-            let expires_at = (U128F0::from_num(balances_as_u128) / U128F0::from_num(price_per_sec)).to_num::<u64>();
+            PendingTransfers::<T>::remove(reservation_id);
 
-            // Update expires at
-            // Calculate based on farmer prices
-            let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
-            let then = (now / 1000) + expires_at;
-            contract.expires_at = then;
-            // Set last claimed in order to know when to contract was deployed
-            contract.last_claimed = now;
-            debug::info!("expires at: {:?}", then);
+            Self::deposit_event(RawEvent::ContractTransferCancelled(reservation_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(6,8)]
+        pub fn accept_contract_transfer(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            ensure!(PendingTransfers::<T>::get(reservation_id) == Some(who.clone()), Error::<T>::TransferNotExists);
+            ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled);
+
+            // The new owner is identified by its own twin
+            let (user_twin_id, _) = pallet_twin::Module::<T>::twin_of(&who).ok_or(Error::<T>::TwinNotExists)?;
+
+            let previous = contract.user_account.clone();
+            PendingTransfers::<T>::remove(reservation_id);
+
+            ReservationsForAccount::<T>::mutate(&previous, |list| list.retain(|id| id != &reservation_id));
+            ReservationsForAccount::<T>::mutate(&who, |list| list.push(reservation_id));
+            if let Some(org_id) = ContractOrg::take(reservation_id) {
+                ReservationsForOrg::mutate(org_id, |list| list.retain(|id| id != &reservation_id));
+            }
+
+            // Refunds of what the previous owner paid in now go to the new one
+            let contributed = Contributions::<T>::take(reservation_id, &previous);
+            Contributions::<T>::mutate(reservation_id, &who, |amount| *amount = amount.saturating_add(contributed));
+            // Top-ups would keep drawing from the previous owner
+            TopUpRules::<T>::remove(reservation_id);
+
+            contract.user_account = who.clone();
+            contract.user_twin_id = user_twin_id;
 
             // Update the contract
             Contracts::<T>::insert(&reservation_id, &contract);
 
-            Self::deposit_event(RawEvent::ContractDeployed(contract.node_id, reservation_id));
+            Self::deposit_event(RawEvent::ContractTransferred(reservation_id, previous, who));
 
             Ok(())
         }
 
-        fn offchain_worker(block_number: T::BlockNumber) {
-            debug::info!("Entering off-chain worker");
+        // Create a contract owned by an organisation, its balance pays for it and gets the refunds
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(5,6)]
+        pub fn create_org_contract(origin, org_id: u64, node_id: Vec<u8>, volume: VolumeType) -> DispatchResult {
+            let who = ensure_signed(origin)?;
 
-            let _ = Self::offchain_signed_tx(block_number);
+            pallet_organisation::Module::<T>::ensure_role(org_id, &who, &[Role::Admin, Role::Deployer])?;
+
+            let org_account = pallet_organisation::Module::<T>::org_account(org_id);
+            let reservation_id = Self::do_create_contract(&who, org_account, node_id, volume)?;
+
+            ContractOrg::insert(reservation_id, org_id);
+            ReservationsForOrg::mutate(org_id, |list| list.push(reservation_id));
+
+            Ok(())
+        }
+
+        // Fund an organisation's contract from its balance, within the member's spending limit
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(4,4)]
+        pub fn pay_from_org(origin, reservation_id: u64, amount: BalanceOf<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let org_id = ContractOrg::get(reservation_id).ok_or(Error::<T>::NotAnOrgContract)?;
+            let spent = amount.saturated_into::<u128>().saturated_into();
+            pallet_organisation::Module::<T>::ensure_can_spend(org_id, &who, spent)?;
+
+            Self::do_pay(pallet_organisation::Module::<T>::org_account(org_id), reservation_id, amount)?;
+            pallet_organisation::Module::<T>::note_spent(org_id, &who, spent);
+
+            Ok(())
+        }
+
+        // An organisation's contract is funded with pay_from_org, so its members' spending limits apply
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn pay(origin, reservation_id: u64, amount: BalanceOf<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(!ContractOrg::contains_key(reservation_id), Error::<T>::OrgContractPayment);
+
+            Self::do_pay(who, reservation_id, amount)
+        }
+
+        // Let another account fund the contract, up to `cap` in total
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn add_sponsor(origin, reservation_id: u64, sponsor: T::AccountId, cap: BalanceOf<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled);
+
+            Sponsors::<T>::insert(reservation_id, &sponsor, cap);
+
+            Self::deposit_event(RawEvent::SponsorAdded(reservation_id, sponsor, cap));
+
+            Ok(())
+        }
+
+        // A removed sponsor can't pay anymore, but is still refunded its share
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn remove_sponsor(origin, reservation_id: u64, sponsor: T::AccountId) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(Sponsors::<T>::contains_key(reservation_id, &sponsor), Error::<T>::NotASponsor);
+
+            Sponsors::<T>::remove(reservation_id, &sponsor);
+
+            Self::deposit_event(RawEvent::SponsorRemoved(reservation_id, sponsor));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(3,3)]
+        pub fn set_auto_top_up(origin, reservation_id: u64, threshold: u64, amount: BalanceOf<T>, cap: BalanceOf<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled);
+
+            // Replacing a rule keeps what it already spent, so the cap can't be reset
+            let previous = TopUpRules::<T>::get(reservation_id);
+            let spent = previous.as_ref().map(|rule| rule.spent).unwrap_or_else(Zero::zero);
+
+            // The rule draws from an organisation's account, what its cap grows by counts against the member's limit
+            let org_spent = ContractOrg::get(reservation_id).map(|org_id| {
+                let previous_cap = previous.as_ref().map(|rule| rule.cap).unwrap_or_else(Zero::zero);
+                (org_id, cap.saturating_sub(previous_cap).saturated_into::<u128>().saturated_into())
+            });
+            if let Some((org_id, added)) = org_spent {
+                pallet_organisation::Module::<T>::ensure_can_spend(org_id, &who, added)?;
+            }
+
+            TopUpRules::<T>::insert(reservation_id, TopUpRule { threshold, amount, cap, spent });
+            if let Some((org_id, added)) = org_spent {
+                pallet_organisation::Module::<T>::note_spent(org_id, &who, added);
+            }
+            Self::schedule_top_up_check(reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::AutoTopUpSet(reservation_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn remove_auto_top_up(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(TopUpRules::<T>::contains_key(reservation_id), Error::<T>::TopUpRuleNotExists);
+
+            TopUpRules::<T>::remove(reservation_id);
+
+            Self::deposit_event(RawEvent::AutoTopUpRemoved(reservation_id));
+
+            Ok(())
+        }
+
+        // Take back prepaid funds the contract does not need to keep running for the minimum duration
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(4,4)]
+        pub fn withdraw(origin, reservation_id: u64, amount: BalanceOf<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled);
+            ensure!(!Disputes::contains_key(reservation_id), Error::<T>::ContractDisputed);
+
+            // What the farmer earned so far is not the user's to take
+            let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
+            let due = if contract.workload_state == WorkloadState::Deployed {
+                Self::amount_due(reservation_id, &contract, now)
+            } else {
+                Zero::zero()
+            };
+            let remaining = <T as Trait>::Currency::free_balance(&contract.account_id).saturating_sub(due);
+            let available = remaining.saturating_sub(<T as Trait>::Currency::minimum_balance());
+            ensure!(amount <= available, Error::<T>::InsufficientContractBalance);
+            // The user takes its share of what is left at most, the rest is the sponsors'
+            let total = Contributions::<T>::iter_prefix(reservation_id)
+                .fold(BalanceOf::<T>::zero(), |total, (_, contributed)| total.saturating_add(contributed));
+            let contributed = Contributions::<T>::get(reservation_id, &contract.user_account);
+            let share = Perbill::from_rational_approximation(contributed, total) * remaining;
+            ensure!(amount <= share, Error::<T>::InsufficientContractBalance);
+
+            let volume = VolumeReservations::get(reservation_id);
+            let runway = Self::prepaid_duration(&contract.resource_prices, &volume, available - amount);
+            ensure!(runway >= Self::minimum_prepaid_duration(&contract.node_id), Error::<T>::InsufficientPrepayment);
+
+            if contract.workload_state == WorkloadState::Deployed {
+                Self::settle_contract(reservation_id, &mut contract)?;
+            }
+
+            // Funds of an organisation's contract go back to the organisation
+            <T as Trait>::Currency::transfer(&contract.account_id, &contract.user_account, amount, KeepAlive)?;
+            Contributions::<T>::insert(reservation_id, &contract.user_account, contributed.saturating_sub(amount));
+
+            // The contract now runs out of funds earlier
+            if contract.expires_at > 0 {
+                Self::reschedule_expiration(reservation_id, &mut contract, now / 1000);
+            }
+
+            // Update the contract
+            Contracts::<T>::insert(&reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::FundsWithdrawn(who, reservation_id, amount));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn set_contract_price(origin, reservation_id: u64, resource_prices: ResourcePrice, farmer_account: <T as frame_system::Trait>::AccountId) -> DispatchResult {
+            // TODO: Only off chain worker can sign this
+            let _ = ensure_signed(origin)?;
+
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            // Only the owner of the node's farm gets paid for the contract
+            ensure!(Self::resolve_farmer(&contract.node_id)? == farmer_account, Error::<T>::UnauthorizedFarmer);
+
+            // A price the user locked in through a quote is not overridden
+            if !PriceLocks::contains_key(reservation_id) {
+                contract.resource_prices = resource_prices;
+            }
+            contract.farmer_account = farmer_account;
+
+            // Update the contract
+            Contracts::<T>::insert(&reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::ContractUpdated(contract.account_id, reservation_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn set_farm_acceptance_policy(origin, farm_id: u64, policy: Option<AcceptancePolicy<T::AccountId>>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(FarmOwner::<T>::get(farm_id) == Some(who), Error::<T>::UnauthorizedFarmer);
+
+            match policy {
+                Some(policy) => FarmAcceptancePolicy::<T>::insert(farm_id, policy),
+                None => FarmAcceptancePolicy::<T>::remove(farm_id),
+            }
+
+            Self::deposit_event(RawEvent::FarmAcceptancePolicySet(farm_id));
+
+            Ok(())
+        }
+
+        // A node's policy takes precedence over the one of its farm
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn set_node_acceptance_policy(origin, node_id: Vec<u8>, policy: Option<AcceptancePolicy<T::AccountId>>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let node = Nodes::get(&node_id).ok_or(Error::<T>::NodeNotExists)?;
+            ensure!(FarmOwner::<T>::get(node.farm_id) == Some(who), Error::<T>::UnauthorizedFarmer);
+
+            match policy {
+                Some(policy) => NodeAcceptancePolicy::<T>::insert(&node_id, policy),
+                None => NodeAcceptancePolicy::<T>::remove(&node_id),
+            }
+
+            Self::deposit_event(RawEvent::NodeAcceptancePolicySet(node_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn accept_contract(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            // Ensure only the farmer of the contract, or its delegate, can accept the contract
+            ensure!(Self::is_authorized(&contract.farmer_account, &who, DelegateScope::AcceptContract), Error::<T>::UnauthorizedFarmer);
+
+            contract.accepted = true;
+
+            // Update the contract
+            Contracts::<T>::insert(&reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::ContractAccepted(contract.node_id, reservation_id));
+
+            Ok(())
+        }
+
+        // Lock in a price the farmer of the contract signed
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,2)]
+        pub fn accept_quote(origin, quote: Quote, signature: T::Signature) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            let reservation_id = quote.reservation_id;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state == WorkloadState::Created, Error::<T>::ContractAlreadyDeployed);
+            // The contract's farmer isn't known before it is priced, the owner of the node's farm is
+            let farmer_account = Self::resolve_farmer(&contract.node_id)?;
+            ensure!(signature.verify(&quote.encode()[..], &farmer_account), Error::<T>::InvalidQuoteSignature);
+
+            let now = <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000;
+            ensure!(now <= quote.valid_until, Error::<T>::QuoteExpired);
+
+            let locked_until = now.saturating_add(quote.lock_duration);
+            contract.resource_prices = quote.resource_prices;
+            PriceLocks::insert(reservation_id, locked_until);
+            PendingPriceChanges::remove(reservation_id);
+
+            // Update the contract
+            Contracts::<T>::insert(&reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::QuoteAccepted(reservation_id, locked_until));
+
+            Ok(())
+        }
+
+        // None falls back to the chain wide minimum
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn set_minimum_prepaid_duration(origin, farm_id: u64, duration: Option<u64>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(FarmOwner::<T>::get(farm_id) == Some(who), Error::<T>::UnauthorizedFarmer);
+
+            match duration {
+                Some(duration) => FarmMinimumPrepaidDuration::insert(farm_id, duration),
+                None => FarmMinimumPrepaidDuration::remove(farm_id),
+            }
+
+            Self::deposit_event(RawEvent::MinimumPrepaidDurationSet(farm_id, duration));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn set_price_change_policy(origin, farm_id: u64, policy: PriceChangePolicy) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(FarmOwner::<T>::get(farm_id) == Some(who), Error::<T>::UnauthorizedFarmer);
+
+            FarmPriceChangePolicy::insert(farm_id, &policy);
+
+            Self::deposit_event(RawEvent::PriceChangePolicySet(farm_id, policy));
+
+            Ok(())
+        }
+
+        // Move the running contracts of a node to new prices, following its farm's price change policy
+        // A farm's nodes are announced one by one, so a call never handles more than MaxContractsPerNode
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(
+            4 + 2 * T::MaxContractsPerNode::get() as Weight,
+            T::MaxContractsPerNode::get() as Weight,
+        )]
+        pub fn announce_price_change(origin, node_id: Vec<u8>, resource_prices: ResourcePrice) -> DispatchResultWithPostInfo {
+            let who = ensure_signed(origin)?;
+
+            let node = Nodes::get(&node_id).ok_or(Error::<T>::NodeNotExists)?;
+            ensure!(FarmOwner::<T>::get(node.farm_id) == Some(who), Error::<T>::UnauthorizedFarmer);
+
+            let now = <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000;
+            let notice = match FarmPriceChangePolicy::get(node.farm_id) {
+                PriceChangePolicy::KeepPrice => return Ok(Some(10_000 + T::DbWeight::get().reads(3)).into()),
+                PriceChangePolicy::Notice(notice) => notice,
+            };
+
+            let mut processed: Weight = 0;
+            for reservation_id in ContractsForNode::get(&node_id) {
+                processed += 1;
+
+                // Prices change at the earliest when the lock ends
+                let effective_at = now.saturating_add(notice).max(PriceLocks::get(reservation_id));
+                PendingPriceChanges::insert(reservation_id, (resource_prices.clone(), effective_at));
+
+                Self::deposit_event(RawEvent::PriceChangeAnnounced(reservation_id, resource_prices.clone(), effective_at));
+            }
+
+            Ok(Some(10_000 + T::DbWeight::get().reads_writes(4 + 2 * processed, processed)).into())
+        }
+
+        // Accept the contract at another price, pending confirmation by the user
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,2)]
+        pub fn counter_offer(origin, reservation_id: u64, resource_prices: ResourcePrice) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            ensure!(Self::is_authorized(&contract.farmer_account, &who, DelegateScope::AcceptContract), Error::<T>::UnauthorizedFarmer);
+            ensure!(contract.workload_state == WorkloadState::Created, Error::<T>::ContractAlreadyDeployed);
+            // The user funded the contract at its price, it can't change under the funds anymore
+            ensure!(contract.expires_at == 0, Error::<T>::ContractAlreadyFunded);
+
+            let expires_at = <system::Module<T>>::block_number() + T::OfferDuration::get();
+            if let Some(previous) = Offers::<T>::get(reservation_id) {
+                OffersExpiringAt::<T>::mutate(previous.expires_at, |list| list.retain(|id| id != &reservation_id));
+            }
+            Offers::<T>::insert(reservation_id, Offer { resource_prices: resource_prices.clone(), expires_at });
+            OffersExpiringAt::<T>::mutate(expires_at, |list| list.push(reservation_id));
+
+            Self::deposit_event(RawEvent::CounterOffered(reservation_id, resource_prices, expires_at));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,3)]
+        pub fn confirm_offer(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled);
+
+            let offer = Self::take_offer(reservation_id)?;
+            ensure!(offer.expires_at > <system::Module<T>>::block_number(), Error::<T>::OfferExpired);
+
+            contract.resource_prices = offer.resource_prices;
+            contract.accepted = true;
+
+            // Update the contract
+            Contracts::<T>::insert(&reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::OfferConfirmed(reservation_id));
+            Self::deposit_event(RawEvent::ContractAccepted(contract.node_id, reservation_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,2)]
+        pub fn reject_offer(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+
+            Self::take_offer(reservation_id)?;
+
+            Self::deposit_event(RawEvent::OfferRejected(reservation_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn claim_funds(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            ensure!(contract.workload_state == WorkloadState::Deployed, Error::<T>::ContractNotDeployed);
+
+            // Ensure only the farmer of the contract can claim the funds
+            ensure!(Self::is_authorized(&contract.farmer_account, &who, DelegateScope::ClaimFunds), Error::<T>::UnauthorizedFarmer);
+
+            let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
+
+            // convert to seconds
+            let diff = (now - contract.last_claimed) / 1000;
+            ensure!(diff > 0, Error::<T>::ClaimError);
+
+            Self::settle_contract(reservation_id, &mut contract)?;
+
+            // Update the contract
+            Contracts::<T>::insert(&reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::ContractFundsClaimed(reservation_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(
+            1 + 5 * T::MaxClaimsPerCall::get() as Weight,
+            1 + 3 * T::MaxClaimsPerCall::get() as Weight,
+        )]
+        pub fn claim_all(origin, cursor: Option<u64>) -> DispatchResultWithPostInfo {
+            let who = ensure_signed(origin)?;
+            let payout_account = Self::payout_account(&who);
+
+            // Contracts are listed by reservation id, so ending contracts don't move the cursor
+            let reservation_ids = ContractsForFarmer::<T>::get(&who);
+            let start = match cursor {
+                Some(cursor) => reservation_ids.iter().position(|id| *id > cursor).unwrap_or(reservation_ids.len()),
+                None => 0,
+            };
+            let end = reservation_ids.len().min(start + T::MaxClaimsPerCall::get() as usize);
+
+            let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
+            let mut claimable = Vec::new();
+            let mut skipped = Vec::new();
+            for reservation_id in &reservation_ids[start..end] {
+                let contract = Contracts::<T>::get(reservation_id);
+                if contract.workload_state != WorkloadState::Deployed {
+                    continue;
+                }
+                match Self::ensure_claimable(*reservation_id) {
+                    Ok(()) => claimable.push((*reservation_id, contract)),
+                    Err(_) => skipped.push(*reservation_id),
+                }
+            }
+
+            // The payout is made once, so only the batch as a whole has to reach the existential deposit
+            let due = claimable.iter().fold(BalanceOf::<T>::zero(), |due, (reservation_id, contract)| {
+                due.saturating_add(Self::amount_due(*reservation_id, contract, now))
+            });
+            Self::ensure_payout_creatable(&payout_account, due)?;
+
+            // Earnings of all contracts are collected first and paid out in a single deposit
+            let mut total = NegativeImbalanceOf::<T>::zero();
+            for (reservation_id, mut contract) in claimable {
+                // A failing contract must not lose what was already withdrawn from the others
+                let earned = match Self::withdraw_due(reservation_id, &mut contract) {
+                    Ok(earned) => earned,
+                    Err(err) => {
+                        debug::info!("error occured: {:?}", err);
+                        skipped.push(reservation_id);
+                        continue;
+                    }
+                };
+                Contracts::<T>::insert(reservation_id, &contract);
+
+                Self::deposit_event(RawEvent::ContractFundsSettled(reservation_id, earned.peek()));
+                total.subsume(earned);
+            }
+            for reservation_id in skipped {
+                Self::deposit_event(RawEvent::ContractClaimSkipped(reservation_id));
+            }
+
+            let amount = total.peek();
+            <T as Trait>::Currency::resolve_creating(&payout_account, total);
+
+            let next = if start < end && end < reservation_ids.len() { Some(reservation_ids[end - 1]) } else { None };
+            Self::deposit_event(RawEvent::AllFundsClaimed(who, amount, next));
+
+            let processed = (end - start) as Weight;
+            Ok(Some(10_000 + T::DbWeight::get().reads_writes(1 + 5 * processed, 1 + 3 * processed)).into())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,2)]
+        pub fn set_payout_address(origin, payout_address: T::AccountId) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // A change that already took effect must not be lost by scheduling the next one
+            Self::apply_payout_address(&who);
+
+            let effective_at = <system::Module<T>>::block_number() + T::PayoutChangeDelay::get();
+            PendingPayoutAddress::<T>::insert(&who, (&payout_address, effective_at));
+
+            Self::deposit_event(RawEvent::PayoutAddressChangeScheduled(who, payout_address, effective_at));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,2)]
+        pub fn cancel_payout_address_change(origin) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            Self::apply_payout_address(&who);
+            ensure!(PendingPayoutAddress::<T>::contains_key(&who), Error::<T>::NoPendingPayoutAddress);
+            PendingPayoutAddress::<T>::remove(&who);
+
+            Self::deposit_event(RawEvent::PayoutAddressChangeCancelled(who));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(0,1)]
+        pub fn add_delegate(origin, delegate: T::AccountId, scopes: Vec<DelegateScope>, expires_at: T::BlockNumber) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(expires_at > <system::Module<T>>::block_number(), Error::<T>::DelegationExpired);
+
+            Delegates::<T>::insert(&who, &delegate, Delegation { scopes, expires_at });
+
+            Self::deposit_event(RawEvent::DelegateAdded(who, delegate, expires_at));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn remove_delegate(origin, delegate: T::AccountId) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(Delegates::<T>::contains_key(&who, &delegate), Error::<T>::DelegateNotExists);
+            Delegates::<T>::remove(&who, &delegate);
+
+            Self::deposit_event(RawEvent::DelegateRemoved(who, delegate));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(0,1)]
+        pub fn set_auto_payout(origin, enabled: bool) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            if enabled {
+                AutoPayout::<T>::insert(&who, true);
+            } else {
+                AutoPayout::<T>::remove(&who);
+            }
+
+            Self::deposit_event(RawEvent::AutoPayoutSet(who, enabled));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn cancel_contract(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            // Ensure only the user of the contract, or a deployer of its organisation, can cancel it
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled);
+            ensure!(!Disputes::contains_key(reservation_id), Error::<T>::ContractDisputed);
+
+            // Cancelling part of a group that is not running yet cancels all of it
+            if let Some(group_id) = Self::pending_group(reservation_id) {
+                Self::rollback_group(group_id);
+                return Ok(());
+            }
+
+            // The farmer is paid for the time the workload ran, the user gets the rest back
+            if contract.workload_state == WorkloadState::Deployed {
+                Self::settle_contract(reservation_id, &mut contract)?;
+            }
+            Self::refund_payers(reservation_id, &contract)?;
+
+            Self::end_contract(reservation_id, &mut contract);
+
+            // Update the contract
+            Contracts::<T>::insert(&reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::ContractCancelled(contract.node_id, reservation_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn contract_cancelled(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            // Ensure the node signed
+            ensure!(Self::is_authorized(&Self::node_account(&contract.node_id)?, &who, DelegateScope::ContractCancelled), Error::<T>::UnauthorizedNode);
+
+            Self::do_contract_cancelled(reservation_id, &mut contract)
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,1)]
+        pub fn contract_deployed(origin, reservation_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            // Ensure the node signed
+            ensure!(Self::is_authorized(&Self::node_account(&contract.node_id)?, &who, DelegateScope::ContractDeployed), Error::<T>::UnauthorizedNode);
+            Self::ensure_reportable(&contract, DeploymentResult::Deployed)?;
+
+            Self::do_contract_deployed(reservation_id, &mut contract)
+        }
+
+        // A deployment result signed by the node, submitted unsigned or by any account relaying it
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(3,2)]
+        pub fn relay_contract_result(origin, reservation_id: u64, result: DeploymentResult, signature: ed25519::Signature) -> DispatchResult {
+            if ensure_none(origin.clone()).is_err() {
+                ensure_signed(origin)?;
+            }
+
+            let (mut contract, pubkey, nonce) = Self::check_contract_result(reservation_id, result, &signature)?;
+            NodeNonce::insert(&pubkey, nonce + 1);
+
+            match result {
+                DeploymentResult::Deployed => Self::do_contract_deployed(reservation_id, &mut contract),
+                DeploymentResult::Cancelled => Self::do_contract_cancelled(reservation_id, &mut contract),
+            }
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,1)]
+        pub fn open_dispute(origin, reservation_id: u64, evidence: Vec<u8>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state == WorkloadState::Deployed, Error::<T>::ContractNotDeployed);
+            ensure!(!Disputes::contains_key(reservation_id), Error::<T>::DisputeExists);
+
+            let now = <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000;
+            ensure!(now <= contract.deployed_at + T::ChallengeWindow::get(), Error::<T>::ChallengeWindowClosed);
+
+            // Farmer claims on the contract are frozen until the dispute is resolved
+            Disputes::insert(reservation_id, Dispute {
+                evidence,
+                opened_at: now,
+                ended_at: None,
+            });
+
+            Self::deposit_event(RawEvent::DisputeOpened(who, reservation_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,3)]
+        pub fn resolve_dispute(origin, reservation_id: u64, refund: Perbill) -> DispatchResult {
+            T::ArbitrationOrigin::ensure_origin(origin)?;
+
+            let dispute = Disputes::get(reservation_id).ok_or(Error::<T>::DisputeNotExists)?;
+            let mut contract = Contracts::<T>::get(reservation_id);
+
+            // Split what the farmer earned since the last claim between the user and the farmer
+            let until = dispute.ended_at.unwrap_or_else(|| <timestamp::Module<T>>::get().saturated_into::<u64>());
+            let due = Self::amount_due(reservation_id, &contract, until);
+            let to_user = refund * due;
+            let to_farmer = due.saturating_sub(to_user);
+
+            <T as Trait>::Currency::transfer(&contract.account_id, &Self::payout_account(&contract.farmer_account), to_farmer, AllowDeath)
+                .map_err(|_| DispatchError::Other("Can't make transfer"))?;
+            <T as Trait>::Currency::transfer(&contract.account_id, &contract.user_account, to_user, AllowDeath)
+                .map_err(|_| DispatchError::Other("Can't make transfer"))?;
+
+            contract.last_claimed = until;
+            contract.downtime = 0;
+            Disputes::remove(reservation_id);
+
+            // A contract that ended while disputed still holds the user's remaining funds
+            if contract.workload_state == WorkloadState::Cancelled {
+                Self::refund_payers(reservation_id, &contract)?;
+            }
+
+            // Update the contract
+            Contracts::<T>::insert(&reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::DisputeResolved(reservation_id, to_user, to_farmer));
+
+            Ok(())
+        }
+
+        // The node co-signs the seeds, so a user can't make it fail challenges with commitments to data it never stored
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(6,4)]
+        pub fn seed_storage_challenges(origin, reservation_id: u64, seeds: Vec<ChallengeSeed>, signature: ed25519::Signature) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            Self::ensure_contract_user(reservation_id, &contract, &who)?;
+            ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled);
+
+            let mut existing = ChallengeSeeds::get(reservation_id);
+            ensure!(existing.len().saturating_add(seeds.len()) <= MAX_CHALLENGE_SEEDS, Error::<T>::TooManyChallengeSeeds);
+
+            let node = Nodes::get(&contract.node_id).ok_or(Error::<T>::NodeNotExists)?;
+            let nonce = NodeNonce::get(&node.pubkey);
+            let payload = (&b"storage_challenge_seeds"[..], reservation_id, &seeds, nonce).encode();
+            ensure!(sp_io::crypto::ed25519_verify(&signature, &payload, &node.pubkey), Error::<T>::InvalidNodeSignature);
+            NodeNonce::insert(&node.pubkey, nonce + 1);
+
+            // A volume already in the challenge cycle keeps the challenge it has
+            if !OpenChallenges::<T>::contains_key(reservation_id) {
+                Self::schedule_challenge(reservation_id);
+            }
+
+            existing.extend(seeds);
+            ChallengeSeeds::insert(reservation_id, existing);
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(3,3)]
+        pub fn respond_storage_challenge(origin, reservation_id: u64, answer: H256) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+            let contract = Contracts::<T>::get(reservation_id);
+
+            // Ensure the node signed
+            ensure!(Self::node_account(&contract.node_id)? == who, Error::<T>::UnauthorizedNode);
+
+            let challenge = OpenChallenges::<T>::take(reservation_id).ok_or(Error::<T>::ChallengeNotExists)?;
+            ChallengeDeadlines::<T>::mutate(challenge.deadline, |list| list.retain(|id| id != &reservation_id));
+
+            if H256::from(sp_io::hashing::blake2_256(answer.as_bytes())) == challenge.seed.commitment {
+                Self::resume_billing(reservation_id);
+                Self::deposit_event(RawEvent::StorageChallengePassed(reservation_id));
+            } else {
+                Self::pause_billing(reservation_id);
+                Self::deposit_event(RawEvent::StorageChallengeFailed(reservation_id));
+            }
+
+            Self::schedule_challenge(reservation_id);
+
+            Ok(())
+        }
+
+        fn on_initialize(n: T::BlockNumber) -> Weight {
+            let issued = ChallengesAt::<T>::take(n);
+            let expired = ChallengeDeadlines::<T>::take(n);
+
+            for reservation_id in &issued {
+                Self::issue_challenge(*reservation_id, n);
+            }
+
+            // Challenges still open at their deadline were missed
+            for reservation_id in &expired {
+                if OpenChallenges::<T>::take(reservation_id).is_some() {
+                    Self::pause_billing(*reservation_id);
+                    Self::deposit_event(RawEvent::StorageChallengeMissed(*reservation_id));
+                    Self::schedule_challenge(*reservation_id);
+                }
+            }
+
+            let processed = (issued.len() + expired.len()) as Weight;
+            let challenge_weight = T::DbWeight::get().reads_writes(2 + processed * 3, 2 + processed * 3);
+
+            challenge_weight + Self::expire_offers(n) + Self::payout_sweep(n)
+        }
+
+        fn offchain_worker(block_number: T::BlockNumber) {
+            debug::info!("Entering off-chain worker");
+
+            let _ = Self::offchain_signed_tx(block_number);
         }
 
         fn on_finalize(b: T::BlockNumber) {
@@ -591,84 +2044,1175 @@ decl_module! {
                 return
             }
 
-            let last_block_time = LastBlockTime::get();
-            debug::info!("last block time: {:?}", last_block_time);
+            let last_block_time = LastBlockTime::get();
+            debug::info!("last block time: {:?}", last_block_time);
+
+            if last_block_time == 0 {
+                LastBlockTime::put(now);
+                return
+            }
+
+            for time in last_block_time..now {
+                // Refill the contracts running low on funds
+                for reservation_id in TopUpChecks::take(time) {
+                    Self::top_up(reservation_id, time);
+                }
+
+                // Get reservationID at a specific timestamp
+                let reservation_ids = ContractPerExpiration::get(time);
+                for reservation_id in reservation_ids {
+                    // A contract that could still be topped up keeps running
+                    if Self::top_up(reservation_id, time) {
+                        continue;
+                    }
+                    match Self::decomission_contract(reservation_id, time) {
+                        Ok(()) => {
+                            debug::info!("decomission of contract: {:?} success", reservation_id)
+                        }
+                        Err(err) => { debug::info!("error occured: {:?}", err); }
+                    }
+                }
+            }
+
+            LastBlockTime::put(now);
+        }
+    }
+}
+
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+    type Call = Call<T>;
+
+    fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+        if let Call::relay_contract_result(reservation_id, result, signature) = call {
+            let (_, pubkey, nonce) = Self::check_contract_result(*reservation_id, *result, signature)
+                .map_err(|_| InvalidTransaction::BadProof)?;
+
+            ValidTransaction::with_tag_prefix("TfgridContractResult")
+                .and_provides((pubkey, nonce))
+                .longevity(64)
+                .propagate(true)
+                .build()
+        } else {
+            InvalidTransaction::Call.into()
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    // Whether `who` is the principal itself, or a delegate allowed to act for it in the scope
+    fn is_authorized(principal: &T::AccountId, who: &T::AccountId, scope: DelegateScope) -> bool {
+        if principal == who {
+            return true;
+        }
+
+        match Delegates::<T>::get(principal, who) {
+            Some(delegation) => {
+                delegation.expires_at > <system::Module<T>>::block_number()
+                    && delegation.scopes.contains(&scope)
+            }
+            None => false,
+        }
+    }
+
+    // Whether `who` may submit `call` without paying fees: a node reporting on one
+    // of its own active contracts, within its fee-free reports for the period
+    pub fn is_free_report(who: &T::AccountId, call: &Call<T>) -> bool {
+        let (reservation_id, result) = match call {
+            Call::contract_deployed(reservation_id) => (*reservation_id, DeploymentResult::Deployed),
+            Call::contract_cancelled(reservation_id) => (*reservation_id, DeploymentResult::Cancelled),
+            _ => return false,
+        };
+
+        if !Contracts::<T>::contains_key(reservation_id) {
+            return false;
+        }
+        let contract = Contracts::<T>::get(reservation_id);
+        // Only a funded contract is active, reports on anything else would be free spam
+        if contract.expires_at == 0 || Self::ensure_reportable(&contract, result).is_err() {
+            return false;
+        }
+        match Self::node_account(&contract.node_id) {
+            Ok(node_account) if &node_account == who => (),
+            _ => return false,
+        }
+
+        Self::free_reports_used(who) < T::MaxFreeReportsPerPeriod::get()
+    }
+
+    // Tag of the free report `who` would make next, the pool keeps one pending transaction per tag
+    // so a node can't queue more free reports than its allowance has left
+    pub fn free_report_tag(who: &T::AccountId) -> Vec<u8> {
+        (&b"node_free_report"[..], who, Self::free_reports_used(who)).encode()
+    }
+
+    // Count a fee-free report against the node's allowance for the current period
+    pub fn note_free_report(who: &T::AccountId) {
+        let now = <system::Module<T>>::block_number();
+        let used = Self::free_reports_used(who);
+        let (period_start, _) = FreeReports::<T>::get(who);
+
+        if used == 0 {
+            FreeReports::<T>::insert(who, (now, 1));
+        } else {
+            FreeReports::<T>::insert(who, (period_start, used + 1));
+        }
+    }
+
+    fn free_reports_used(who: &T::AccountId) -> u32 {
+        let (period_start, used) = FreeReports::<T>::get(who);
+        if <system::Module<T>>::block_number() >= period_start + T::FreeReportPeriod::get() {
+            return 0;
+        }
+
+        used
+    }
+
+    // Verify a node's signature over (reservation_id, result, nonce) against the key its id encodes
+    fn check_contract_result(
+        reservation_id: u64,
+        result: DeploymentResult,
+        signature: &ed25519::Signature,
+    ) -> Result<(Contract<T>, ed25519::Public, u64), DispatchError> {
+        ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+        let contract = Contracts::<T>::get(reservation_id);
+
+        let node = Nodes::get(&contract.node_id).ok_or(Error::<T>::NodeNotExists)?;
+        let pubkey = Self::node_key(&contract.node_id).ok_or(Error::<T>::InvalidNodeSignature)?;
+        ensure!(pubkey == node.pubkey, Error::<T>::InvalidNodeSignature);
+
+        let nonce = NodeNonce::get(&pubkey);
+        // The genesis hash keeps a result signed for another chain from being relayed here
+        let genesis_hash = <system::Module<T>>::block_hash(T::BlockNumber::zero());
+        let payload = (genesis_hash, reservation_id, result, nonce).encode();
+        ensure!(sp_io::crypto::ed25519_verify(signature, &payload, &pubkey), Error::<T>::InvalidNodeSignature);
+
+        Self::ensure_reportable(&contract, result)?;
+
+        Ok((contract, pubkey, nonce))
+    }
+
+    // A workload is deployed once, and can't be reported cancelled after it ended
+    fn ensure_reportable(contract: &Contract<T>, result: DeploymentResult) -> DispatchResult {
+        match result {
+            DeploymentResult::Deployed => ensure!(contract.workload_state == WorkloadState::Created, Error::<T>::ContractAlreadyDeployed),
+            DeploymentResult::Cancelled => ensure!(contract.workload_state != WorkloadState::Cancelled, Error::<T>::ContractCancelled),
+        }
+
+        Ok(())
+    }
+
+    // Node ids are the bs58 encoding of the node's ed25519 key
+    fn node_key(node_id: &[u8]) -> Option<ed25519::Public> {
+        let bytes = bs58::decode(node_id).into_vec().ok()?;
+        let raw: [u8; 32] = bytes.as_slice().try_into().ok()?;
+
+        Some(ed25519::Public::from_raw(raw))
+    }
+
+    fn policy_accepts(contract: &Contract<T>, volume: &VolumeType) -> bool {
+        let policy = match NodeAcceptancePolicy::<T>::get(&contract.node_id) {
+            Some(policy) => policy,
+            None => match Nodes::get(&contract.node_id).and_then(|node| FarmAcceptancePolicy::<T>::get(node.farm_id)) {
+                Some(policy) => policy,
+                None => return false,
+            },
+        };
+
+        // A contract that pays nothing is never accepted for the farmer
+        if Self::get_price_per_hour(&contract.resource_prices, volume.get_rsu()) == I32F32::from_num(0) {
+            return false;
+        }
+        let prepaid_duration = Self::prepaid_duration(&contract.resource_prices, volume, <T as Trait>::Currency::free_balance(&contract.account_id));
+
+        policy.accepts(&contract.user_account, volume, &contract.resource_prices, prepaid_duration)
+    }
+
+    // Refill the contract when its runway at `now` dropped below its rule's threshold, returns whether it was
+    fn top_up(reservation_id: u64, now: u64) -> bool {
+        let mut rule = match TopUpRules::<T>::get(reservation_id) {
+            Some(rule) => rule,
+            None => return false,
+        };
+        let mut contract = Contracts::<T>::get(reservation_id);
+        if contract.workload_state == WorkloadState::Cancelled || contract.expires_at == 0 {
+            return false;
+        }
+
+        if contract.expires_at.saturating_sub(now) > rule.threshold {
+            Self::schedule_top_up_check(reservation_id, &contract);
+            return false;
+        }
+
+        let amount = rule.amount.min(rule.cap.saturating_sub(rule.spent));
+        if amount.is_zero() {
+            TopUpRules::<T>::remove(reservation_id);
+            Self::deposit_event(RawEvent::TopUpCapReached(reservation_id));
+            return false;
+        }
+
+        if <T as Trait>::Currency::transfer(&contract.user_account, &contract.account_id, amount, KeepAlive).is_err() {
+            Self::deposit_event(RawEvent::TopUpFailed(reservation_id, amount));
+            return false;
+        }
+        rule.spent = rule.spent.saturating_add(amount);
+        TopUpRules::<T>::insert(reservation_id, &rule);
+        Contributions::<T>::mutate(reservation_id, &contract.user_account, |contributed| {
+            *contributed = contributed.saturating_add(amount)
+        });
+
+        // The refill extends the contract by the time it pays for
+        let volume = VolumeReservations::get(reservation_id);
+        Self::remove_expiration(reservation_id, contract.expires_at);
+        contract.expires_at = contract
+            .expires_at
+            .max(now)
+            .saturating_add(Self::prepaid_duration(&contract.resource_prices, &volume, amount));
+        ContractPerExpiration::mutate(&contract.expires_at, |list| list.push(reservation_id));
+        Contracts::<T>::insert(reservation_id, &contract);
+
+        Self::schedule_top_up_check(reservation_id, &contract);
+        Self::deposit_event(RawEvent::ContractToppedUp(reservation_id, amount));
+
+        true
+    }
+
+    fn schedule_top_up_check(reservation_id: u64, contract: &Contract<T>) {
+        let threshold = match TopUpRules::<T>::get(reservation_id) {
+            Some(rule) => rule.threshold,
+            None => return,
+        };
+        if contract.expires_at == 0 {
+            return;
+        }
+
+        let now = <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000;
+        let check_at = contract.expires_at.saturating_sub(threshold).max(now + 1);
+        TopUpChecks::mutate(check_at, |list| {
+            if !list.contains(&reservation_id) {
+                list.push(reservation_id)
+            }
+        });
+    }
+
+    fn minimum_prepaid_duration(node_id: &[u8]) -> u64 {
+        Nodes::get(node_id)
+            .and_then(|node| FarmMinimumPrepaidDuration::get(node.farm_id))
+            .unwrap_or_else(T::MinimumPrepaidDuration::get)
+    }
+
+    // Seconds the contract's balance pays for at its current price
+    fn prepaid_duration(resource_prices: &ResourcePrice, volume: &VolumeType, balance: BalanceOf<T>) -> u64 {
+        let price_per_hour = Self::get_price_per_hour(resource_prices, volume.get_rsu());
+        let price_per_sec = U64F64::from_num(price_per_hour) * U64F64::from_num(1e12) / U64F64::from_num(3600);
+        let price_per_sec = U128F0::from_num(price_per_sec);
+        if price_per_sec == U128F0::from_num(0) {
+            return u64::max_value();
+        }
+
+        let balances_as_u128: u128 = balance.saturated_into::<u128>();
+
+        (U128F0::from_num(balances_as_u128) / price_per_sec).saturating_to_num::<u64>()
+    }
+
+    // The contract's user, or for a contract owned by an organisation, one of its deployers
+    fn ensure_contract_user(reservation_id: u64, contract: &Contract<T>, who: &T::AccountId) -> DispatchResult {
+        if &contract.user_account == who {
+            return Ok(());
+        }
+
+        let org_id = ContractOrg::get(reservation_id).ok_or(Error::<T>::UnauthorizedUser)?;
+        pallet_organisation::Module::<T>::ensure_role(org_id, who, &[Role::Admin, Role::Deployer])?;
+
+        Ok(())
+    }
+
+    // The contract's user, or for a contract owned by an organisation, one of its admins
+    fn ensure_contract_owner(reservation_id: u64, contract: &Contract<T>, who: &T::AccountId) -> DispatchResult {
+        if &contract.user_account == who {
+            return Ok(());
+        }
+
+        let org_id = ContractOrg::get(reservation_id).ok_or(Error::<T>::UnauthorizedUser)?;
+        pallet_organisation::Module::<T>::ensure_role(org_id, who, &[Role::Admin])?;
+
+        Ok(())
+    }
+
+    fn do_pay(who: T::AccountId, reservation_id: u64, amount: BalanceOf<T>) -> DispatchResult {
+        ensure!(Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractNotExists);
+
+        let mut contract = Contracts::<T>::get(reservation_id);
+
+        let (_, contributor, contributed) = Self::ensure_can_pay(&who, reservation_id, &contract, amount)?;
+        let farmer_account = Self::resolve_farmer(&contract.node_id)?;
+
+        debug::info!("Transfering: {:?} from {:?} to contract accountId: {:?}", &amount, &who, &contract.account_id);
+        // Transfer currency to the contracts account
+        <T as Trait>::Currency::transfer(&who, &contract.account_id, amount, AllowDeath)
+            .map_err(|_| DispatchError::Other("Can't make transfer"))?;
+        Contributions::<T>::insert(reservation_id, &contributor, contributed);
+
+        // The workload's resources are held on the node from the first payment for as long as the contract lives
+        if !ReservedCapacity::contains_key(reservation_id) {
+            Self::reserve_capacity(reservation_id, &contract.node_id, &VolumeReservations::get(reservation_id).get_resources())?;
+            ContractsForNode::mutate(&contract.node_id, |list| list.push(reservation_id));
+
+            // The farmer claims the contract from then on, its list stays sorted for claim_all's cursor
+            contract.farmer_account = farmer_account;
+            ContractsForFarmer::<T>::mutate(&contract.farmer_account, |list| {
+                if let Err(index) = list.binary_search(&reservation_id) {
+                    list.insert(index, reservation_id)
+                }
+            });
+        }
+
+        // The whole balance runs from the last claim of a deployed contract, nothing was billed before that.
+        // Refilling an already funded contract does not add on top of its old expiration, that runway is in the balance
+        let since = if contract.workload_state == WorkloadState::Deployed {
+            contract.last_claimed / 1000
+        } else {
+            <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000
+        };
+        Self::reschedule_expiration(reservation_id, &mut contract, since);
+        debug::info!("Contract will expire at: {:?}", &contract.expires_at);
+
+        // Accept on behalf of the farmer once the contract is funded as its policy asks
+        let auto_accepted = !contract.accepted && Self::policy_accepts(&contract, &VolumeReservations::get(reservation_id));
+        contract.accepted = contract.accepted || auto_accepted;
+
+        // Update the contract
+        Contracts::<T>::insert(&reservation_id, &contract);
+        if TopUpRules::<T>::contains_key(reservation_id) {
+            Self::schedule_top_up_check(reservation_id, &contract);
+        }
+
+        Self::deposit_event(RawEvent::ContractPaid(contract.account_id.clone(), reservation_id));
+        if auto_accepted {
+            Self::deposit_event(RawEvent::ContractAccepted(contract.node_id, reservation_id));
+        }
+
+        Ok(())
+    }
+
+    // Checks a payment of `amount` by `who` has to pass, returns the seconds the contract's
+    // balance would pay for, whose contribution the payment counts as and what that would total
+    fn ensure_can_pay(
+        who: &T::AccountId,
+        reservation_id: u64,
+        contract: &Contract<T>,
+        amount: BalanceOf<T>,
+    ) -> Result<(u64, T::AccountId, BalanceOf<T>), DispatchError> {
+        // ensure!(contract.accepted == true, Error::<T>::ContractNotAccepted);
+        // The user has to settle on the farmer's counter-offer before funding the contract
+        ensure!(!Offers::<T>::contains_key(reservation_id), Error::<T>::OfferPending);
+
+        // Reevauluate contract expiration date
+        // check if the expiration date is set first in order to not confuse the user
+        let volume = VolumeReservations::get(reservation_id);
+
+        let balance = <T as Trait>::Currency::free_balance(&contract.account_id).saturating_add(amount);
+        let expires_at = Self::prepaid_duration(&contract.resource_prices, &volume, balance);
+        // Without a price the contract would never expire
+        ensure!(expires_at != u64::max_value(), Error::<T>::ContractNotPriced);
+
+        // The first payment has to keep the workload running for a meaningful time
+        if contract.expires_at == 0 {
+            ensure!(expires_at >= Self::minimum_prepaid_duration(&contract.node_id), Error::<T>::InsufficientPrepayment);
+        }
+        if !ReservedCapacity::contains_key(reservation_id) {
+            ensure!(volume.get_resources().fits_in(&NodeCapacity::get(&contract.node_id).free()), Error::<T>::NodeCapacityExceeded);
+            ensure!(
+                (ContractsForNode::get(&contract.node_id).len() as u32) < T::MaxContractsPerNode::get(),
+                Error::<T>::NodeContractLimitReached
+            );
+        }
+
+        // Once the contract has sponsors anyone but the user funds it as a sponsor, within its cap,
+        // until then anyone can fund the contract for its user
+        let has_sponsors = Sponsors::<T>::iter_prefix(reservation_id).next().is_some();
+        let contributor = if has_sponsors { who.clone() } else { contract.user_account.clone() };
+        let contributed = Contributions::<T>::get(reservation_id, &contributor).saturating_add(amount);
+        if contributor != contract.user_account {
+            let cap = Sponsors::<T>::get(reservation_id, &contributor).ok_or(Error::<T>::NotASponsor)?;
+            ensure!(contributed <= cap, Error::<T>::SponsorCapExceeded);
+        }
+
+        Ok((expires_at, contributor, contributed))
+    }
+
+    // Create a contract owned by `owner`, on behalf of `creator` whose twin identifies the user
+    fn do_create_contract(
+        creator: &T::AccountId,
+        owner: T::AccountId,
+        node_id: Vec<u8>,
+        volume: VolumeType,
+    ) -> Result<u64, DispatchError> {
+        ensure!(Nodes::contains_key(&node_id), Error::<T>::NodeNotExists);
+        ensure!(volume.is_valid(), Error::<T>::InvalidVolume);
+
+        // Users are identified by their twin
+        let (user_twin_id, _) = pallet_twin::Module::<T>::twin_of(creator).ok_or(Error::<T>::TwinNotExists)?;
+
+        let reservation_id = ReservationID::get();
+
+        ensure!(!Contracts::<T>::contains_key(&reservation_id), Error::<T>::ContractExists);
+
+        // Create a contract
+        let mut contract = Contract::default();
+
+        contract.node_id = node_id.clone();
+        debug::info!("Contract with id: {:?} and nodeId: {:?}", reservation_id, contract.node_id);
+
+        // Create a new accountID based on the reservationID and assign it to the contract
+        let account_id = PALLET_ID.into_sub_account(reservation_id);
+        let _ = <T as Trait>::Currency::make_free_balance_be(
+            &account_id,
+            <T as Trait>::Currency::minimum_balance(),
+        );
+        debug::info!("Assigned accountID: {:?} to contract with id: {:?}", account_id, reservation_id);
+        contract.account_id = account_id;
+
+        // Add the user account to the contract
+        contract.user_account = owner.clone();
+        contract.user_twin_id = user_twin_id;
+
+        // Update the contract
+        Contracts::<T>::insert(&reservation_id, &contract);
+
+        // TODO, make generic for each workload type
+        VolumeReservations::insert(reservation_id, &volume);
+        ReservationID::put(reservation_id + 1);
+
+        ReservationsForAccount::<T>::mutate(&owner, |list|  list.push(reservation_id));
+
+        Self::deposit_event(RawEvent::ContractAdded(owner, node_id, reservation_id));
+
+        Ok(reservation_id)
+    }
+
+    fn do_contract_cancelled(reservation_id: u64, contract: &mut Contract<T>) -> DispatchResult {
+        // Nothing of a group that failed to deploy is billed
+        if let Some(group_id) = Self::pending_group(reservation_id) {
+            Self::rollback_group(group_id);
+            *contract = Contracts::<T>::get(reservation_id);
+
+            return Ok(());
+        }
+
+        // The workload is gone, settle it like a cancellation by the user
+        if Disputes::contains_key(reservation_id) {
+            Self::hold_disputed(reservation_id);
+        } else if contract.workload_state == WorkloadState::Deployed {
+            Self::settle_contract(reservation_id, contract)?;
+
+            // Dropping a running workload without the user asking for it breaches the SLA
+            if let Some(node) = Nodes::get(&contract.node_id) {
+                Self::slash_farm(node.farm_id, vec![contract.user_account.clone()], Perbill::one());
+            }
+        }
+        if contract.workload_state != WorkloadState::Cancelled && !Disputes::contains_key(reservation_id) {
+            Self::refund_payers(reservation_id, contract)?;
+        }
+
+        Self::end_contract(reservation_id, contract);
+
+        // Update the contract
+        Contracts::<T>::insert(&reservation_id, &*contract);
+
+        Self::deposit_event(RawEvent::ContractUpdated(contract.account_id.clone(), reservation_id));
+
+        Ok(())
+    }
+
+    fn do_contract_deployed(reservation_id: u64, contract: &mut Contract<T>) -> DispatchResult {
+        contract.workload_state = WorkloadState::Deployed;
+
+        // Update expires at
+        // Calculate based on farmer prices
+        let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
+        Self::reschedule_expiration(reservation_id, contract, now / 1000);
+        // Set last claimed in order to know when to contract was deployed
+        contract.last_claimed = now;
+        contract.deployed_at = now / 1000;
+        debug::info!("expires at: {:?}", contract.expires_at);
+
+        // Update the contract
+        Contracts::<T>::insert(&reservation_id, &*contract);
+
+        Self::deposit_event(RawEvent::ContractDeployed(contract.node_id.clone(), reservation_id));
+
+        if let Some(group_id) = Self::pending_group(reservation_id) {
+            Self::activate_group(group_id);
+            *contract = Contracts::<T>::get(reservation_id);
+        }
+
+        Ok(())
+    }
+
+    fn pending_group(reservation_id: u64) -> Option<u64> {
+        let group_id = GroupOf::get(reservation_id)?;
+        match Groups::<T>::get(group_id) {
+            Some(group) if group.state == GroupState::Pending => Some(group_id),
+            _ => None,
+        }
+    }
+
+    // Start billing the contracts of a group once the last of them is deployed
+    fn activate_group(group_id: u64) {
+        let mut group = match Groups::<T>::get(group_id) {
+            Some(group) => group,
+            None => return,
+        };
+        let deployed = group
+            .reservation_ids
+            .iter()
+            .all(|id| Contracts::<T>::get(id).workload_state == WorkloadState::Deployed);
+        if !deployed {
+            return;
+        }
+
+        // Billing and the prepaid time of all contracts start once the whole group runs
+        let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
+        for reservation_id in &group.reservation_ids {
+            let mut contract = Contracts::<T>::get(reservation_id);
+            contract.last_claimed = now;
+            contract.deployed_at = now / 1000;
+            Self::reschedule_expiration(*reservation_id, &mut contract, now / 1000);
+            Contracts::<T>::insert(reservation_id, &contract);
+        }
+        group.state = GroupState::Active;
+        Groups::<T>::insert(group_id, &group);
+
+        Self::deposit_event(RawEvent::GroupActivated(group_id));
+    }
+
+    // End all contracts of a group that could not be fully deployed, refunding everything paid in
+    fn rollback_group(group_id: u64) {
+        let mut group = match Groups::<T>::get(group_id) {
+            Some(group) => group,
+            None => return,
+        };
+
+        for reservation_id in &group.reservation_ids {
+            let mut contract = Contracts::<T>::get(reservation_id);
+            if contract.workload_state == WorkloadState::Cancelled {
+                continue;
+            }
+
+            if let Err(err) = Self::refund_payers(*reservation_id, &contract) {
+                debug::info!("error occured: {:?}", err);
+            }
+            Self::end_contract(*reservation_id, &mut contract);
+            Contracts::<T>::insert(reservation_id, &contract);
+
+            Self::deposit_event(RawEvent::ContractCancelled(contract.node_id, *reservation_id));
+        }
+        group.state = GroupState::RolledBack;
+        Groups::<T>::insert(group_id, &group);
+
+        Self::deposit_event(RawEvent::GroupRolledBack(group_id));
+    }
+
+    fn node_account(node_id: &[u8]) -> Result<T::AccountId, DispatchError> {
+        let node = Nodes::get(node_id).ok_or(Error::<T>::NodeNotExists)?;
+
+        Ok(T::AccountId::decode(&mut &node.pubkey[..]).unwrap_or_default())
+    }
+
+    fn decomission_contract(reservation_id: u64, time: u64) -> Result<(), DispatchError> {
+        let mut contract = Contracts::<T>::get(reservation_id);
+        debug::info!(
+            "contract: {:?} for reservation ID {:?} found at time: {:?}",
+            contract.account_id,
+            reservation_id,
+            time
+        );
+
+        if let Some(group_id) = Self::pending_group(reservation_id) {
+            Self::rollback_group(group_id);
+            return Ok(());
+        }
+
+        if Disputes::contains_key(reservation_id) {
+            // Funds stay in the contract until the dispute is resolved
+            Self::hold_disputed(reservation_id);
+        } else {
+            if contract.workload_state == WorkloadState::Deployed {
+                let amount = Self::settle_contract(reservation_id, &mut contract)?;
+                debug::info!(
+                    "Settled {:?} from contract: {:?} to farmer: {:?}",
+                    &amount,
+                    &contract.account_id,
+                    &contract.farmer_account
+                );
+            }
+
+            // Whatever the farmer did not earn, e.g. during downtime, goes back to the user
+            Self::refund_payers(reservation_id, &contract)?;
+        }
+
+        Self::end_contract(reservation_id, &mut contract);
+
+        // Update the contract
+        Contracts::<T>::insert(&reservation_id, &contract);
+
+        Self::deposit_event(RawEvent::ContractCancelled(
+            contract.node_id,
+            reservation_id,
+        ));
+
+        Ok(())
+    }
 
-            if last_block_time == 0 {
-                LastBlockTime::put(now);
-                return
+    // Pays the farmer what the contract earned since it was last claimed
+    fn settle_contract(
+        reservation_id: u64,
+        contract: &mut Contract<T>,
+    ) -> Result<BalanceOf<T>, DispatchError> {
+        let payout_account = Self::payout_account(&contract.farmer_account);
+        let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
+        Self::ensure_payout_creatable(&payout_account, Self::amount_due(reservation_id, contract, now))?;
+
+        let earned = Self::withdraw_due(reservation_id, contract)?;
+        let amount = earned.peek();
+
+        // Transfer currency to the farmers payout account
+        <T as Trait>::Currency::resolve_creating(&payout_account, earned);
+
+        Ok(amount)
+    }
+
+    /// The account a farmer's earnings are paid to, the farmer itself unless it registered another one.
+    pub fn payout_account(farmer: &T::AccountId) -> T::AccountId {
+        if let Some((payout_address, effective_at)) = PendingPayoutAddress::<T>::get(farmer) {
+            if effective_at <= <system::Module<T>>::block_number() {
+                return payout_address;
             }
+        }
 
-            for time in last_block_time..now {
-                // Get reservationID at a specific timestamp
-                let reservation_ids = ContractPerExpiration::get(time);
-                for reservation_id in reservation_ids {
-                    match Self::decomission_contract(reservation_id, time) {
-                        Ok(()) => {
-                            debug::info!("decomission of contract: {:?} success", reservation_id)
-                        }
-                        Err(err) => { debug::info!("error occured: {:?}", err); }
-                    }
-                }
+        PayoutAddress::<T>::get(farmer).unwrap_or_else(|| farmer.clone())
+    }
+
+    fn apply_payout_address(farmer: &T::AccountId) {
+        if let Some((payout_address, effective_at)) = PendingPayoutAddress::<T>::get(farmer) {
+            if effective_at <= <system::Module<T>>::block_number() {
+                PayoutAddress::<T>::insert(farmer, payout_address);
+                PendingPayoutAddress::<T>::remove(farmer);
             }
+        }
+    }
 
-            LastBlockTime::put(now);
+    // Takes what the contract earned since it was last claimed out of the contract's account
+    // Earnings of a disputed contract or of one in a group that isn't active yet stay on the contract
+    fn ensure_claimable(reservation_id: u64) -> DispatchResult {
+        ensure!(
+            !Disputes::contains_key(reservation_id),
+            Error::<T>::ContractDisputed
+        );
+        ensure!(
+            Self::pending_group(reservation_id).is_none(),
+            Error::<T>::GroupNotActive
+        );
+
+        Ok(())
+    }
+
+    // Creating the payout account with less than the existential deposit would burn the amount
+    fn ensure_payout_creatable(payout_account: &T::AccountId, amount: BalanceOf<T>) -> DispatchResult {
+        ensure!(
+            amount.is_zero()
+                || amount >= <T as Trait>::Currency::minimum_balance()
+                || !<T as Trait>::Currency::total_balance(payout_account).is_zero(),
+            Error::<T>::PayoutBelowExistentialDeposit
+        );
+
+        Ok(())
+    }
+
+    fn withdraw_due(
+        reservation_id: u64,
+        contract: &mut Contract<T>,
+    ) -> Result<NegativeImbalanceOf<T>, DispatchError> {
+        Self::ensure_claimable(reservation_id)?;
+
+        let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
+        let amount = Self::amount_due(reservation_id, contract, now);
+
+        let earned = <T as Trait>::Currency::withdraw(
+            &contract.account_id,
+            amount,
+            WithdrawReasons::from(WithdrawReason::Transfer),
+            AllowDeath,
+        )?;
+
+        contract.last_claimed = now;
+        contract.downtime = 0;
+
+        // Billed at the old price up to here, the new one applies from now on
+        Self::apply_price_change(reservation_id, contract, now / 1000);
+
+        Ok(earned)
+    }
+
+    fn apply_price_change(reservation_id: u64, contract: &mut Contract<T>, now: u64) {
+        match PendingPriceChanges::get(reservation_id) {
+            Some((resource_prices, effective_at)) if effective_at <= now => {
+                contract.resource_prices = resource_prices;
+                PendingPriceChanges::remove(reservation_id);
+                PriceLocks::remove(reservation_id);
+                Self::reschedule_expiration(reservation_id, contract, now);
+
+                Self::deposit_event(RawEvent::PriceChanged(reservation_id));
+            }
+            _ => (),
         }
     }
-}
 
-impl<T: Trait> Module<T> {
-    fn decomission_contract(reservation_id: u64, time: u64) -> Result<(), DispatchError> {
-        let mut contract = Contracts::<T>::get(reservation_id);
+    // Move the contract's expiration to when its balance runs out at its current price
+    fn reschedule_expiration(reservation_id: u64, contract: &mut Contract<T>, now: u64) {
+        let volume = VolumeReservations::get(reservation_id);
+        let prepaid_duration = Self::prepaid_duration(&contract.resource_prices, &volume, <T as Trait>::Currency::free_balance(&contract.account_id));
+
+        Self::remove_expiration(reservation_id, contract.expires_at);
+        contract.expires_at = now.saturating_add(prepaid_duration);
+        ContractPerExpiration::mutate(&contract.expires_at, |list| list.push(reservation_id));
+    }
+
+    // What the contract owes its farmer up to `now`, capped by the contract's balance
+    fn amount_due(reservation_id: u64, contract: &Contract<T>, now: u64) -> BalanceOf<T> {
+        let seconds = Self::billable_seconds(contract, now);
+        let price_per_sec = Self::price_per_sec(reservation_id, contract);
+
         debug::info!(
-            "contract: {:?} for reservation ID {:?} found at time: {:?}",
-            contract.account_id,
-            reservation_id,
-            time
+            "{:?} billable seconds since last claimed, price per sec: {:?}",
+            seconds,
+            price_per_sec
         );
 
-        // Get the contract's balance
-        let balance: BalanceOf<T> = T::Currency::free_balance(&contract.account_id);
+        let amount_to_claim = (U64F64::from_num(seconds) * price_per_sec).to_num::<u128>();
+        let balance: BalanceOf<T> = <T as Trait>::Currency::free_balance(&contract.account_id);
+        let balance_as_u128 = balance.saturated_into::<u128>();
 
         debug::info!(
-            "Transfering {:?} from contract: {:?} to farmer: {:?}",
-            &balance,
+            "Trying to claim {:?}, from contract with balance: {:?}",
+            &amount_to_claim,
+            &balance_as_u128
+        );
+
+        amount_to_claim.min(balance_as_u128).saturated_into()
+    }
+
+    fn hold_disputed(reservation_id: u64) {
+        let now = <timestamp::Module<T>>::get().saturated_into::<u64>();
+
+        Disputes::mutate(reservation_id, |dispute| {
+            if let Some(dispute) = dispute {
+                dispute.ended_at.get_or_insert(now);
+            }
+        });
+    }
+
+    // Returns the contract's balance to everyone who paid into it, pro-rata to what they contributed
+    fn refund_payers(reservation_id: u64, contract: &Contract<T>) -> DispatchResult {
+        // The existential deposit the contract's account was created with isn't the payers' money
+        let seed = <T as Trait>::Currency::minimum_balance();
+        let balance: BalanceOf<T> = <T as Trait>::Currency::free_balance(&contract.account_id).saturating_sub(seed);
+
+        let contributions: Vec<(T::AccountId, BalanceOf<T>)> =
+            Contributions::<T>::iter_prefix(reservation_id).collect();
+        let total = contributions
+            .iter()
+            .fold(BalanceOf::<T>::zero(), |total, (_, amount)| total.saturating_add(*amount));
+
+        let mut refunded = BalanceOf::<T>::zero();
+        for (payer, contributed) in contributions {
+            // The user gets what is left after rounding
+            if payer == contract.user_account {
+                continue;
+            }
+
+            let share = Perbill::from_rational_approximation(contributed, total) * balance;
+            debug::info!(
+                "Transfering: {:?} from contract {:?} to sponsor {:?}",
+                &share,
+                &contract.account_id,
+                &payer
+            );
+            <T as Trait>::Currency::transfer(&contract.account_id, &payer, share, AllowDeath)
+                .map_err(|_| DispatchError::Other("Can't make transfer"))?;
+            refunded = refunded.saturating_add(share);
+
+            Self::deposit_event(RawEvent::ContributionRefunded(reservation_id, payer, share));
+        }
+
+        let remainder = balance.saturating_sub(refunded);
+        debug::info!(
+            "Transfering: {:?} from contract {:?} to user {:?}",
+            &remainder,
             &contract.account_id,
-            &contract.farmer_account
+            &contract.user_account
         );
         // Transfer currency to the users account
-        T::Currency::transfer(
+        <T as Trait>::Currency::transfer(
             &contract.account_id,
-            &contract.farmer_account,
-            balance,
+            &contract.user_account,
+            remainder,
             AllowDeath,
         )
-        .map_err(|err| {
-            debug::info!("{:?}", err);
-            err
-        })?;
+        .map_err(|_| DispatchError::Other("Can't make transfer"))?;
+        // It was minted when the contract was created, burning it again reaps the account
+        let _ = <T as Trait>::Currency::slash(&contract.account_id, seed);
+
+        Contributions::<T>::remove_prefix(reservation_id);
 
+        Ok(())
+    }
+
+    // Moves a contract to its final state, freeing what it held on its node
+    fn end_contract(reservation_id: u64, contract: &mut Contract<T>) {
+        if contract.workload_state != WorkloadState::Cancelled {
+            Self::release_capacity(reservation_id, contract);
+            ContractsForNode::mutate(&contract.node_id, |list| {
+                list.retain(|id| id != &reservation_id)
+            });
+            ContractsForFarmer::<T>::mutate(&contract.farmer_account, |list| {
+                list.retain(|id| id != &reservation_id)
+            });
+        }
+        Self::remove_expiration(reservation_id, contract.expires_at);
+        PriceLocks::remove(reservation_id);
+        PendingPriceChanges::remove(reservation_id);
+        TopUpRules::<T>::remove(reservation_id);
+        Sponsors::<T>::remove_prefix(reservation_id);
+        PendingTransfers::<T>::remove(reservation_id);
+        PendingUpdates::remove(reservation_id);
         contract.workload_state = WorkloadState::Cancelled;
+    }
 
-        // Update the contract
-        Contracts::<T>::insert(&reservation_id, &contract);
+    // Seconds to bill since the last claim, leaving out the node's downtime
+    fn billable_seconds(contract: &Contract<T>, now: u64) -> u64 {
+        let since = contract.last_claimed / 1000;
+        let elapsed = (now / 1000).saturating_sub(since);
 
-        ContractPerExpiration::mutate(&contract.expires_at, |list| {
-            debug::info!("list: {:?}", list);
-            if list.len() > 0 {
-                let index = list.iter().position(|x| x == &reservation_id).unwrap();
-                list.remove(index);
+        // An outage that is still going on has not been noted by a heartbeat yet
+        let ongoing = Self::ongoing_outage(contract, since, now / 1000);
+
+        // Paused billing usually overlaps with an outage, so only the longest counts
+        let paused = if contract.billing_paused_at > 0 {
+            (now / 1000).saturating_sub(contract.billing_paused_at.max(since))
+        } else {
+            0
+        };
+        let downtime = contract.downtime + ongoing.max(paused);
+
+        elapsed.saturating_sub(downtime)
+    }
+
+    // The node was last seen at its last heartbeat, or when it reported the contract deployed
+    fn outage_start(contract: &Contract<T>, last_heartbeat: u64) -> u64 {
+        last_heartbeat.max(contract.deployed_at) + T::HeartbeatInterval::get()
+    }
+
+    fn ongoing_outage(contract: &Contract<T>, since: u64, now: u64) -> u64 {
+        let outage_start = Self::outage_start(contract, LastHeartbeat::get(&contract.node_id)).max(since);
+        now.saturating_sub(outage_start)
+    }
+
+    // Credits the contracts of a node for the outage that ends `now`, returns the contracts it went through
+    fn note_outage(node_id: &[u8], last_heartbeat: u64, now: u64) -> Weight {
+        let reservation_ids = ContractsForNode::get(node_id);
+        let mut affected_users = Vec::new();
+
+        for reservation_id in &reservation_ids {
+            let mut contract = Contracts::<T>::get(reservation_id);
+            if contract.workload_state != WorkloadState::Deployed {
+                continue;
             }
-        });
 
-        Self::deposit_event(RawEvent::ContractCancelled(
+            // Only the part of the outage that has not been claimed yet counts
+            let outage_start = Self::outage_start(&contract, last_heartbeat).max(contract.last_claimed / 1000);
+            if now > outage_start {
+                contract.downtime += now - outage_start;
+                Contracts::<T>::insert(reservation_id, &contract);
+                affected_users.push(contract.user_account);
+            }
+        }
+
+        // Without an earlier heartbeat the outage only counts for the contracts, from their deployment
+        if last_heartbeat > 0 {
+            let start = last_heartbeat + T::HeartbeatInterval::get();
+            Self::deposit_event(RawEvent::NodeOutage(node_id.to_vec(), start, now));
+
+            if let Some(node) = Nodes::get(node_id) {
+                Self::slash_farm(node.farm_id, affected_users, Self::outage_slash(now - start));
+            }
+        }
+
+        reservation_ids.len() as Weight
+    }
+
+    // Part of the SlashFraction an outage costs: nothing within the grace period, then growing with its length
+    fn outage_slash(duration: u64) -> Perbill {
+        let full = T::SlashDowntime::get().max(1);
+        let slashed = duration.saturating_sub(T::OutageGracePeriod::get()).min(full);
+
+        Perbill::from_rational_approximation(slashed, full)
+    }
+
+    // Splits `scale` of the SlashFraction of the farm's bond over the users affected by a breach
+    fn slash_farm(farm_id: u64, users: Vec<T::AccountId>, scale: Perbill) {
+        let owner = match FarmOwner::<T>::get(farm_id) {
+            Some(owner) => owner,
+            None => return,
+        };
+
+        let bond = FarmBond::<T>::get(farm_id);
+        let slash = scale * (T::SlashFraction::get() * bond);
+        if users.is_empty() || slash.is_zero() {
+            return;
+        }
+        let per_user = slash / BalanceOf::<T>::from(users.len() as u32);
+
+        let mut slashed = BalanceOf::<T>::zero();
+        for user in users {
+            // repatriate_reserved returns the part it could not move
+            let remaining = <T as Trait>::Currency::repatriate_reserved(&owner, &user, per_user, BalanceStatus::Free)
+                .unwrap_or(per_user);
+            let amount = per_user.saturating_sub(remaining);
+            slashed = slashed.saturating_add(amount);
+
+            debug::info!("Slashed {:?} from farm {:?} to user {:?}", &amount, farm_id, &user);
+            Self::deposit_event(RawEvent::FarmerSlashed(farm_id, user, amount));
+        }
+
+        FarmBond::<T>::insert(farm_id, bond.saturating_sub(slashed));
+    }
+
+    fn farm_units(farm_id: u64) -> u64 {
+        NodesForFarm::get(farm_id)
+            .iter()
+            .map(|node_id| NodeCapacity::get(node_id).total.units())
+            .fold(0, |total, units| total.saturating_add(units))
+    }
+
+    fn bond_for(units: u64) -> BalanceOf<T> {
+        T::BondPerUnit::get().saturating_mul(units.saturated_into())
+    }
+
+    // A zero AutoPayoutPeriod never starts a sweep
+    fn sweep_starts_at(n: T::BlockNumber) -> bool {
+        let period = T::AutoPayoutPeriod::get();
+        !period.is_zero() && (n % period).is_zero()
+    }
+
+    // Settles a bounded batch of contracts for farmers with automatic payouts, a sweep
+    // starts every AutoPayoutPeriod blocks and continues over the next blocks until done
+    fn payout_sweep(n: T::BlockNumber) -> Weight {
+        let cursor = match PayoutCursor::get() {
+            Some(cursor) => cursor,
+            None if Self::sweep_starts_at(n) => 0,
+            None => return T::DbWeight::get().reads(1),
+        };
+
+        let end = ReservationID::get();
+        let last = end.min(cursor.saturating_add(T::MaxPayoutsPerBlock::get() as u64));
+
+        for reservation_id in cursor..last {
+            let mut contract = Contracts::<T>::get(reservation_id);
+            if contract.workload_state != WorkloadState::Deployed
+                || !AutoPayout::<T>::get(&contract.farmer_account)
+                || Disputes::contains_key(reservation_id)
+            {
+                continue;
+            }
+
+            match Self::settle_contract(reservation_id, &mut contract) {
+                Ok(amount) => {
+                    debug::info!("Paid out {:?} for contract {:?}", amount, reservation_id);
+                    Contracts::<T>::insert(reservation_id, &contract);
+                    Self::deposit_event(RawEvent::ContractFundsClaimed(reservation_id));
+                }
+                Err(err) => {
+                    debug::info!("error occured: {:?}", err);
+                }
+            }
+        }
+
+        if last < end {
+            PayoutCursor::put(last);
+        } else {
+            PayoutCursor::kill();
+        }
+
+        let processed = last.saturating_sub(cursor) as Weight;
+        T::DbWeight::get().reads_writes(2 + processed * 6, 1 + processed * 3)
+    }
+
+    fn take_offer(reservation_id: u64) -> Result<Offer<T::BlockNumber>, DispatchError> {
+        let offer = Offers::<T>::take(reservation_id).ok_or(Error::<T>::OfferNotExists)?;
+        OffersExpiringAt::<T>::mutate(offer.expires_at, |list| list.retain(|id| id != &reservation_id));
+
+        Ok(offer)
+    }
+
+    // Counter-offers the user did not confirm in time lapse, the contract keeps its price
+    fn expire_offers(n: T::BlockNumber) -> Weight {
+        let expired = OffersExpiringAt::<T>::take(n);
+
+        for reservation_id in &expired {
+            Offers::<T>::remove(reservation_id);
+            Self::deposit_event(RawEvent::OfferExpired(*reservation_id));
+        }
+
+        T::DbWeight::get().reads_writes(1, 1 + expired.len() as Weight)
+    }
+
+    fn schedule_challenge(reservation_id: u64) {
+        if ChallengeScheduledAt::<T>::contains_key(reservation_id) {
+            return;
+        }
+
+        let at = <system::Module<T>>::block_number() + T::StorageChallengeInterval::get();
+        ChallengesAt::<T>::mutate(at, |list| list.push(reservation_id));
+        ChallengeScheduledAt::<T>::insert(reservation_id, at);
+    }
+
+    fn issue_challenge(reservation_id: u64, n: T::BlockNumber) {
+        ChallengeScheduledAt::<T>::remove(reservation_id);
+
+        let contract = Contracts::<T>::get(reservation_id);
+        let mut seeds = ChallengeSeeds::get(reservation_id);
+
+        // Cancelled contracts and exhausted seeds drop out of the challenge cycle
+        if contract.workload_state == WorkloadState::Cancelled || seeds.is_empty() {
+            return;
+        }
+        if contract.workload_state != WorkloadState::Deployed {
+            Self::schedule_challenge(reservation_id);
+            return;
+        }
+
+        let random = T::RandomnessSource::random(&(&b"storage_challenge"[..], reservation_id).encode());
+        let index = (random.to_low_u64_be() % seeds.len() as u64) as usize;
+
+        // Every seed is used once, since the answer is public after it was given
+        let seed = seeds.swap_remove(index);
+        ChallengeSeeds::insert(reservation_id, seeds);
+
+        let deadline = n + T::StorageChallengeTimeout::get();
+        ChallengeDeadlines::<T>::mutate(deadline, |list| list.push(reservation_id));
+
+        Self::deposit_event(RawEvent::StorageChallengeIssued(
             contract.node_id,
             reservation_id,
+            seed.offset,
+            seed.length,
         ));
 
+        OpenChallenges::<T>::insert(reservation_id, StorageChallenge { seed, deadline });
+    }
+
+    fn pause_billing(reservation_id: u64) {
+        let mut contract = Contracts::<T>::get(reservation_id);
+        if contract.billing_paused_at > 0 {
+            return;
+        }
+
+        contract.billing_paused_at = <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000;
+        Contracts::<T>::insert(reservation_id, &contract);
+
+        Self::deposit_event(RawEvent::BillingPaused(reservation_id));
+    }
+
+    fn resume_billing(reservation_id: u64) {
+        let mut contract = Contracts::<T>::get(reservation_id);
+        if contract.billing_paused_at == 0 {
+            return;
+        }
+
+        // The paused time that has not been claimed yet is not billed
+        let now = <timestamp::Module<T>>::get().saturated_into::<u64>() / 1000;
+        let paused_from = contract.billing_paused_at.max(contract.last_claimed / 1000);
+        contract.downtime += now.saturating_sub(paused_from);
+        contract.billing_paused_at = 0;
+        Contracts::<T>::insert(reservation_id, &contract);
+
+        Self::deposit_event(RawEvent::BillingResumed(reservation_id));
+    }
+
+    fn price_per_sec(reservation_id: u64, contract: &Contract<T>) -> U64F64 {
+        let volume = VolumeReservations::get(reservation_id);
+
+        let price_per_hour = Self::get_price_per_hour(&contract.resource_prices, volume.get_rsu());
+        U64F64::from_num(price_per_hour) * U64F64::from_num(1e12) / U64F64::from_num(3600)
+    }
+
+    fn reserve_capacity(reservation_id: u64, node_id: &[u8], resources: &Resources) -> DispatchResult {
+        let mut capacity = NodeCapacity::get(node_id);
+        ensure!(resources.fits_in(&capacity.free()), Error::<T>::NodeCapacityExceeded);
+
+        capacity.reserved = capacity.reserved.add(resources);
+        NodeCapacity::insert(node_id, &capacity);
+        ReservedCapacity::insert(reservation_id, resources);
+
+        Ok(())
+    }
+
+    // Swap the resources a contract holds on its node for new ones
+    fn resize_capacity(reservation_id: u64, node_id: &[u8], previous: &Resources, resources: &Resources) -> DispatchResult {
+        let mut capacity = NodeCapacity::get(node_id);
+        ensure!(resources.fits_in(&capacity.free().add(previous)), Error::<T>::NodeCapacityExceeded);
+
+        capacity.reserved = capacity.reserved.sub(previous).add(resources);
+        NodeCapacity::insert(node_id, &capacity);
+        ReservedCapacity::insert(reservation_id, resources);
+
         Ok(())
     }
 
+    // A contract that was never funded holds nothing on its node
+    fn release_capacity(reservation_id: u64, contract: &Contract<T>) {
+        if let Some(resources) = ReservedCapacity::take(reservation_id) {
+            NodeCapacity::mutate(&contract.node_id, |capacity| {
+                capacity.reserved = capacity.reserved.sub(&resources);
+            });
+        }
+    }
+
+    fn remove_expiration(reservation_id: u64, expires_at: u64) {
+        ContractPerExpiration::mutate(expires_at, |list| {
+            debug::info!("list: {:?}", list);
+            list.retain(|id| id != &reservation_id);
+        });
+    }
+
+    /// Resolve the pricing a farm published in its DID document.
+    pub fn did_pricing(did: &[u8]) -> Option<ResourcePrice> {
+        let document = pallet_did::Module::<T>::resolve_active(did)?;
+        let service = document.service(PRICING_SERVICE)?;
+
+        ResourcePrice::decode(&mut &service.endpoint[..]).ok()
+    }
+
+    /// Resolve the ed25519 key a node is identified by in its DID document.
+    pub fn did_node_key(did: &[u8]) -> Option<ed25519::Public> {
+        let document = pallet_did::Module::<T>::resolve_active(did)?;
+
+        document
+            .key(pallet_did::KeyType::Ed25519)
+            .map(ed25519::Public::from_raw)
+    }
+
+    // Pricing and farmer of a node, when its farm is fully defined on chain
+    fn farm_pricing_from_chain(node_id: &[u8]) -> Option<(ResourcePrice, T::AccountId)> {
+        let node = Nodes::get(node_id)?;
+        let did = FarmDid::get(node.farm_id)?;
+        let resource_prices = Self::did_pricing(&did)?;
+        let farmer_account = Self::resolve_farmer(node_id).ok()?;
+
+        Some((resource_prices, farmer_account))
+    }
+
     fn get_price_per_hour(resource_prices: &ResourcePrice, rsu: RSU) -> I32F32 {
         let cru = resource_prices.cru * rsu.cru;
         let hru = I32F32::from_num(resource_prices.hru) * rsu.hru;
@@ -706,30 +3250,35 @@ impl<T: Trait> Module<T> {
             contract.node_id
         );
 
-        let farm = Self::fetch_farmer_prices(contract.node_id).map_err(|err| {
-            debug::info!("{:?}", err);
-            err
-        })?;
+        // Farms that publish their pricing in a DID don't need the explorer
+        let (resource_prices, farmer_account) = match Self::farm_pricing_from_chain(&contract.node_id) {
+            Some(pricing) => pricing,
+            None => {
+                let farm = Self::fetch_farmer_prices(contract.node_id).map_err(|err| {
+                    debug::info!("{:?}", err);
+                    err
+                })?;
+
+                debug::info!(
+                    "Farm: {:?} is owned by: {:?}",
+                    farm.farm_info.id,
+                    farm.farmer_account
+                );
+
+                (farm.farm_info.resource_prices[0].clone(), farm.farmer_account)
+            }
+        };
 
         reservation_id_storage.set(&reservation_id);
 
-        debug::info!(
-            "Pubkey before parsing hex farm: {:?}, {:?}",
-            farm.farm_info.id,
-            farm.pubkey
-        );
-
-        let decoded = <[u8; 32]>::from_hex(farm.pubkey.clone()).expect("Decoding failed");
-        let farmer_address = ed25519::Public::from_raw(decoded);
-
         // retrieve contract account
         let signer = Signer::<T, T::AuthorityId>::any_account();
 
         let result = signer.send_signed_transaction(|_acct| {
             Call::set_contract_price(
                 reservation_id,
-                farm.farm_info.resource_prices[0].clone(),
-                T::AccountId::decode(&mut &farmer_address[..]).unwrap_or_default(),
+                resource_prices.clone(),
+                farmer_account.clone(),
             )
         });
 
@@ -747,7 +3296,7 @@ impl<T: Trait> Module<T> {
         return Err(<Error<T>>::NoLocalAcctForSignedTx);
     }
 
-    fn fetch_farmer_prices(node_id: Vec<u8>) -> Result<Farm, Error<T>> {
+    fn fetch_farmer_prices(node_id: Vec<u8>) -> Result<Farm<T::AccountId>, Error<T>> {
         let mut lock = StorageLock::<BlockAndTime<Self>>::with_block_and_time_deadline(
             b"offchain-explorer::lock",
             LOCK_BLOCK_EXPIRATION,
@@ -755,13 +3304,13 @@ impl<T: Trait> Module<T> {
         );
 
         if let Ok(_guard) = lock.try_lock() {
-            match Self::fetch_n_parse_node(node_id) {
+            match Self::fetch_n_parse_node(node_id.clone()) {
                 Ok(node_info) => match Self::fetch_n_parse_farm(node_info.farm_id) {
-                    Ok(farm_info) => match Self::fetch_n_parse_user(farm_info.threebot_id) {
-                        Ok(user_info) => {
+                    Ok(farm_info) => match Self::resolve_farmer(&node_id) {
+                        Ok(farmer_account) => {
                             let farm = Farm {
                                 farm_info,
-                                pubkey: user_info.pubkey,
+                                farmer_account,
                             };
                             return Ok(farm);
                         }
@@ -826,26 +3375,16 @@ impl<T: Trait> Module<T> {
         Ok(farm_info)
     }
 
-    fn fetch_n_parse_user(threebot_id: u64) -> Result<UserInfo, Error<T>> {
-        debug::info!("fetching user");
-        let resp_bytes = Self::fetch_user_from_remote(threebot_id).map_err(|e| {
-            debug::error!("fetch_node_from_remote error: {:?}", e);
-            <Error<T>>::HttpFetchingError
-        })?;
-
-        let resp_str = str::from_utf8(&resp_bytes).map_err(|err| {
-            debug::info!("{:?}", err);
-            <Error<T>>::HttpFetchingError
-        })?;
-        // debug::info!("{}", resp_str);
-
-        let user_info: UserInfo = serde_json::from_str(&resp_str).map_err(|err| {
-            debug::info!("{:?}", err);
-            <Error<T>>::HttpFetchingError
-        })?;
-        debug::info!("got farm response");
+    fn resolve_farmer(node_id: &[u8]) -> Result<T::AccountId, Error<T>> {
+        // The farmer is the twin of the owner of the farm the node registered for on chain, the explorer
+        // only provides the prices since it can't prove who owns a farm
+        let node = Nodes::get(node_id).ok_or(<Error<T>>::NodeNotExists)?;
+        let owner = FarmOwner::<T>::get(node.farm_id).ok_or(<Error<T>>::FarmNotExists)?;
+        let (twin_id, twin) = pallet_twin::Module::<T>::twin_of(&owner).ok_or(<Error<T>>::TwinNotExists)?;
+        let farmer_account = twin.ed25519_account;
+        debug::info!("resolved farmer of farm: {:?} to twin: {:?}", node.farm_id, twin_id);
 
-        Ok(user_info)
+        Ok(farmer_account)
     }
 
     /// This function uses the `offchain::http` API to query the remote github information,
@@ -911,37 +3450,6 @@ impl<T: Trait> Module<T> {
 
         Ok(response.body().collect::<Vec<u8>>())
     }
-
-    fn fetch_user_from_remote(threebot_id: u64) -> Result<Vec<u8>, Error<T>> {
-        let mut h = EXPLORER_USERS.as_bytes().to_vec();
-        h.extend_from_slice(&to_str_bytes(threebot_id));
-
-        let p = str::from_utf8(&h).unwrap();
-
-        debug::info!("sending request to: {:?}", p);
-
-        let request = rt_offchain::http::Request::get(&p);
-
-        let timeout = sp_io::offchain::timestamp()
-            .add(rt_offchain::Duration::from_millis(FETCH_TIMEOUT_PERIOD));
-
-        let pending = request
-            .deadline(timeout) // Setting the timeout time
-            .send() // Sending the request out by the host
-            .map_err(|_| <Error<T>>::HttpFetchingError)?;
-
-        let response = pending
-            .try_wait(timeout)
-            .map_err(|_| <Error<T>>::HttpFetchingError)?
-            .map_err(|_| <Error<T>>::HttpFetchingError)?;
-
-        if response.code != 200 {
-            debug::error!("Unexpected http request status code: {}", response.code);
-            return Err(<Error<T>>::HttpFetchingError);
-        }
-
-        Ok(response.body().collect::<Vec<u8>>())
-    }
 }
 
 fn to_str_bytes(mut number: u64) -> Vec<u8> {
@@ -967,20 +3475,3 @@ impl<T: Trait> rt_offchain::storage_lock::BlockNumberProvider for Module<T> {
         <frame_system::Module<T>>::block_number()
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::to_str_bytes;
-    use sp_std::str;
-
-    #[test]
-    fn number_to_str_bytes() {
-        let case1 = (3242, "3242");
-        let case2 = (002342, "2342");
-        let case3 = (1234567890, "1234567890");
-
-        assert_eq!(case1.1, str::from_utf8(&to_str_bytes(case1.0)).unwrap());
-        assert_eq!(case2.1, str::from_utf8(&to_str_bytes(case2.0)).unwrap());
-        assert_eq!(case3.1, str::from_utf8(&to_str_bytes(case3.0)).unwrap());
-    }
-}
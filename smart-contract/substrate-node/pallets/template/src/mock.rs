@@ -1,8 +1,15 @@
-use crate::{Module, Trait};
-use sp_core::H256;
-use frame_support::{impl_outer_origin, parameter_types, weights::Weight};
+use crate::{crypto, Module, Trait};
+use sp_core::{sr25519, Pair, H256};
+use frame_support::{
+	impl_outer_origin, parameter_types,
+	traits::{Get, Randomness},
+	weights::Weight,
+};
+use std::cell::RefCell;
 use sp_runtime::{
-	traits::{BlakeTwo256, IdentityLookup}, testing::Header, Perbill,
+	testing::{Header, TestXt},
+	traits::{BlakeTwo256, Extrinsic as ExtrinsicT, IdentityLookup, Verify},
+	Perbill,
 };
 use frame_system as system;
 
@@ -21,6 +28,9 @@ parameter_types! {
 	pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
 }
 
+pub type AccountId = sr25519::Public;
+pub type Balance = u64;
+
 impl system::Trait for Test {
 	type BaseCallFilter = ();
 	type Origin = Origin;
@@ -29,7 +39,7 @@ impl system::Trait for Test {
 	type BlockNumber = u64;
 	type Hash = H256;
 	type Hashing = BlakeTwo256;
-	type AccountId = u64;
+	type AccountId = AccountId;
 	type Lookup = IdentityLookup<Self::AccountId>;
 	type Header = Header;
 	type Event = ();
@@ -42,20 +52,213 @@ impl system::Trait for Test {
 	type MaximumBlockLength = MaximumBlockLength;
 	type AvailableBlockRatio = AvailableBlockRatio;
 	type Version = ();
-	type ModuleToIndex = ();
-	type AccountData = ();
+	type PalletInfo = ();
+	type AccountData = pallet_balances::AccountData<Balance>;
 	type OnNewAccount = ();
 	type OnKilledAccount = ();
 	type SystemWeightInfo = ();
 }
 
+thread_local! {
+	static EXISTENTIAL_DEPOSIT: RefCell<Balance> = RefCell::new(1);
+	static AUTO_PAYOUT_PERIOD: RefCell<u64> = RefCell::new(10);
+}
+
+// Tests raise the existential deposit to check amounts below it
+pub struct ExistentialDeposit;
+impl Get<Balance> for ExistentialDeposit {
+	fn get() -> Balance {
+		EXISTENTIAL_DEPOSIT.with(|v| *v.borrow())
+	}
+}
+
+pub fn set_existential_deposit(amount: Balance) {
+	EXISTENTIAL_DEPOSIT.with(|v| *v.borrow_mut() = amount);
+}
+
+parameter_types! {
+	pub const MaxLocks: u32 = 50;
+}
+
+// Tests turn automatic payouts off with a zero period
+pub struct AutoPayoutPeriod;
+impl Get<u64> for AutoPayoutPeriod {
+	fn get() -> u64 {
+		AUTO_PAYOUT_PERIOD.with(|v| *v.borrow())
+	}
+}
+
+pub fn set_auto_payout_period(blocks: u64) {
+	AUTO_PAYOUT_PERIOD.with(|v| *v.borrow_mut() = blocks);
+}
+
+impl pallet_balances::Trait for Test {
+	type MaxLocks = MaxLocks;
+	type Balance = Balance;
+	type Event = ();
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const MinimumPeriod: u64 = 1;
+}
+
+impl pallet_timestamp::Trait for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = MinimumPeriod;
+	type WeightInfo = ();
+}
+
+impl pallet_twin::Trait for Test {
+	type Event = ();
+}
+
+impl pallet_did::Trait for Test {
+	type Event = ();
+}
+
+impl pallet_organisation::Trait for Test {
+	type Event = ();
+	type Currency = Balances;
+}
+
+pub type Extrinsic = TestXt<crate::Call<Test>, ()>;
+
+impl system::offchain::SigningTypes for Test {
+	type Public = <sr25519::Signature as Verify>::Signer;
+	type Signature = sr25519::Signature;
+}
+
+impl<LocalCall> system::offchain::SendTransactionTypes<LocalCall> for Test
+where
+	crate::Call<Test>: From<LocalCall>,
+{
+	type OverarchingCall = crate::Call<Test>;
+	type Extrinsic = Extrinsic;
+}
+
+impl<LocalCall> system::offchain::CreateSignedTransaction<LocalCall> for Test
+where
+	crate::Call<Test>: From<LocalCall>,
+{
+	fn create_transaction<C: system::offchain::AppCrypto<Self::Public, Self::Signature>>(
+		call: crate::Call<Test>,
+		_public: <sr25519::Signature as Verify>::Signer,
+		_account: AccountId,
+		nonce: u64,
+	) -> Option<(crate::Call<Test>, <Extrinsic as ExtrinsicT>::SignaturePayload)> {
+		Some((call, (nonce, ())))
+	}
+}
+
+pub struct TestRandomness;
+
+impl Randomness<H256> for TestRandomness {
+	fn random(subject: &[u8]) -> H256 {
+		H256::from(sp_io::hashing::blake2_256(subject))
+	}
+}
+
+parameter_types! {
+	pub const HeartbeatInterval: u64 = 10 * 60;
+	pub const BondPerUnit: Balance = 1_000;
+	pub const SlashFraction: Perbill = Perbill::from_percent(10);
+	pub const OutageGracePeriod: u64 = 10 * 60;
+	pub const SlashDowntime: u64 = 100 * 60;
+	pub const ChallengeWindow: u64 = 24 * 60 * 60;
+	pub const StorageChallengeInterval: u64 = 10;
+	pub const StorageChallengeTimeout: u64 = 5;
+	pub const MaxPayoutsPerBlock: u32 = 2;
+	pub const MaxClaimsPerCall: u32 = 2;
+	pub const PayoutChangeDelay: u64 = 10;
+	pub const FreeReportPeriod: u64 = 10;
+	pub const MaxFreeReportsPerPeriod: u32 = 2;
+	pub const OfferDuration: u64 = 10;
+	pub const MinimumPrepaidDuration: u64 = 60 * 60;
+	pub const MaxContractsPerNode: u32 = 3;
+}
+
 impl Trait for Test {
 	type Event = ();
+	type Currency = Balances;
+	type RandomnessSource = TestRandomness;
+	type AuthorityId = crypto::TestAuthId;
+	type HeartbeatInterval = HeartbeatInterval;
+	type BondPerUnit = BondPerUnit;
+	type SlashFraction = SlashFraction;
+	type OutageGracePeriod = OutageGracePeriod;
+	type SlashDowntime = SlashDowntime;
+	type ArbitrationOrigin = system::EnsureRoot<AccountId>;
+	type ChallengeWindow = ChallengeWindow;
+	type StorageChallengeInterval = StorageChallengeInterval;
+	type StorageChallengeTimeout = StorageChallengeTimeout;
+	type AutoPayoutPeriod = AutoPayoutPeriod;
+	type MaxPayoutsPerBlock = MaxPayoutsPerBlock;
+	type MaxClaimsPerCall = MaxClaimsPerCall;
+	type PayoutChangeDelay = PayoutChangeDelay;
+	type FreeReportPeriod = FreeReportPeriod;
+	type MaxFreeReportsPerPeriod = MaxFreeReportsPerPeriod;
+	type OfferDuration = OfferDuration;
+	type MinimumPrepaidDuration = MinimumPrepaidDuration;
+	type MaxContractsPerNode = MaxContractsPerNode;
 }
 
+pub type System = system::Module<Test>;
+pub type Balances = pallet_balances::Module<Test>;
+pub type Timestamp = pallet_timestamp::Module<Test>;
+pub type TwinModule = pallet_twin::Module<Test>;
+pub type OrganisationModule = pallet_organisation::Module<Test>;
 pub type TemplateModule = Module<Test>;
 
+// Every test account starts with this much
+pub const INITIAL_BALANCE: Balance = 1_000_000_000_000_000_000;
+
+pub fn account(seed: u8) -> AccountId {
+	sr25519::Public::from_raw([seed; 32])
+}
+
+pub fn alice() -> AccountId {
+	account(1)
+}
+
+pub fn bob() -> AccountId {
+	account(2)
+}
+
+pub fn charlie() -> AccountId {
+	account(3)
+}
+
+// The farmer signs quotes, so its account comes from a key
+pub fn farmer_pair() -> sr25519::Pair {
+	sr25519::Pair::from_seed(&[10; 32])
+}
+
+pub fn farmer() -> AccountId {
+	farmer_pair().public()
+}
+
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-	system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+	set_existential_deposit(1);
+	set_auto_payout_period(10);
+	let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	pallet_balances::GenesisConfig::<Test> {
+		balances: vec![
+			(alice(), INITIAL_BALANCE),
+			(bob(), INITIAL_BALANCE),
+			(charlie(), INITIAL_BALANCE),
+			(farmer(), INITIAL_BALANCE),
+		],
+	}
+	.assimilate_storage(&mut t)
+	.unwrap();
+
+	let mut ext: sp_io::TestExternalities = t.into();
+	ext.execute_with(|| System::set_block_number(1));
+	ext
 }
@@ -0,0 +1,1886 @@
+use super::*;
+use crate::mock::*;
+use frame_support::{
+    assert_noop, assert_ok,
+    traits::{OnFinalize, OnInitialize},
+};
+use sp_core::{sr25519, Pair};
+
+const FARM_ID: u64 = 0;
+const HOUR: u64 = 60 * 60;
+// What a contract on `volume()` costs per second at `prices()`
+const PRICE_PER_SEC: Balance = 10_000_000_000;
+
+fn set_time(secs: u64) {
+    Timestamp::set_timestamp(secs * 1000);
+}
+
+fn node_pair(seed: u8) -> ed25519::Pair {
+    ed25519::Pair::from_seed(&[seed; 32])
+}
+
+fn node_account(seed: u8) -> AccountId {
+    AccountId::from_raw(node_pair(seed).public().0)
+}
+
+fn node_id(seed: u8) -> Vec<u8> {
+    bs58::encode(&node_pair(seed).public()[..]).into_vec()
+}
+
+fn create_twin(who: AccountId) -> u64 {
+    assert_ok!(TwinModule::create_twin(
+        Origin::signed(who),
+        None,
+        None,
+        vec![]
+    ));
+
+    TwinModule::twin_id_by_account(who).unwrap()
+}
+
+// Register a node for the farmer's farm and report its capacity
+fn register_node(seed: u8) -> Vec<u8> {
+    if TemplateModule::farm_owner(FARM_ID).is_none() {
+        create_twin(farmer());
+        assert_ok!(TemplateModule::create_farm(Origin::signed(farmer())));
+    }
+
+    let pair = node_pair(seed);
+    let pubkey = pair.public();
+    let nonce = TemplateModule::node_nonce(&pubkey);
+    let signature = pair.sign(&(FARM_ID, farmer(), nonce).encode());
+    assert_ok!(TemplateModule::register_node(
+        Origin::signed(farmer()),
+        FARM_ID,
+        pubkey,
+        signature
+    ));
+
+    let capacity = Resources {
+        cru: 4,
+        mru: 16,
+        sru: 100,
+        hru: 100,
+    };
+    assert_ok!(TemplateModule::bond(
+        Origin::signed(farmer()),
+        FARM_ID,
+        BondPerUnit::get() * capacity.units()
+    ));
+    assert_ok!(TemplateModule::report_capacity(
+        Origin::signed(node_account(seed)),
+        node_id(seed),
+        capacity
+    ));
+
+    node_id(seed)
+}
+
+fn volume() -> VolumeType {
+    VolumeType {
+        disk_type: 1,
+        size: 1,
+    }
+}
+
+fn prices() -> ResourcePrice {
+    ResourcePrice {
+        hru: 36,
+        ..ResourcePrice::default()
+    }
+}
+
+fn create_contract_for(who: AccountId, node_id: Vec<u8>, volume: VolumeType) -> u64 {
+    let reservation_id = ReservationID::get();
+    assert_ok!(TemplateModule::create_contract(
+        Origin::signed(who),
+        node_id,
+        volume
+    ));
+    assert_ok!(TemplateModule::set_contract_price(
+        Origin::signed(who),
+        reservation_id,
+        prices(),
+        farmer()
+    ));
+
+    reservation_id
+}
+
+// A contract of alice on a fresh node, priced but not funded
+fn setup_contract() -> u64 {
+    set_time(1_000);
+    create_twin(alice());
+    let node_id = register_node(1);
+
+    create_contract_for(alice(), node_id, volume())
+}
+
+fn fund(reservation_id: u64, seconds: u64) {
+    assert_ok!(TemplateModule::pay(
+        Origin::signed(alice()),
+        reservation_id,
+        PRICE_PER_SEC * seconds
+    ));
+}
+
+// Report the contract deployed by the node with the given seed
+fn deploy(seed: u8, reservation_id: u64) {
+    assert_ok!(TemplateModule::contract_deployed(
+        Origin::signed(node_account(seed)),
+        reservation_id
+    ));
+}
+
+fn contract_balance(reservation_id: u64) -> Balance {
+    Balances::free_balance(&TemplateModule::contracts(reservation_id).account_id)
+}
+
+#[test]
+fn nodes_are_only_registered_by_the_farm_owner() {
+    new_test_ext().execute_with(|| {
+        let pair = node_pair(1);
+        let signature = pair.sign(&(FARM_ID, bob(), 0u64).encode());
+
+        // A farm has to exist before nodes can join it
+        assert_noop!(
+            TemplateModule::register_node(
+                Origin::signed(bob()),
+                FARM_ID,
+                pair.public(),
+                signature.clone()
+            ),
+            Error::<Test>::FarmNotExists
+        );
+
+        // Nor can a farm be created without a twin to pay its farmer on
+        assert_noop!(
+            TemplateModule::create_farm(Origin::signed(farmer())),
+            Error::<Test>::TwinNotExists
+        );
+        create_twin(farmer());
+        assert_ok!(TemplateModule::create_farm(Origin::signed(farmer())));
+        assert_eq!(TemplateModule::farm_owner(FARM_ID), Some(farmer()));
+
+        // Registering the first node does not make anyone else the farm's owner
+        assert_noop!(
+            TemplateModule::register_node(Origin::signed(bob()), FARM_ID, pair.public(), signature),
+            Error::<Test>::UnauthorizedFarmer
+        );
+
+        let signature = pair.sign(&(FARM_ID, farmer(), 0u64).encode());
+        assert_ok!(TemplateModule::register_node(
+            Origin::signed(farmer()),
+            FARM_ID,
+            pair.public(),
+            signature
+        ));
+        assert_eq!(TemplateModule::nodes_for_farm(FARM_ID), vec![node_id(1)]);
+        assert_eq!(TemplateModule::farm_owner(FARM_ID), Some(farmer()));
+    });
+}
+
+#[test]
+fn twin_ids_are_allocated_by_the_chain() {
+    new_test_ext().execute_with(|| {
+        let alice_twin = create_twin(alice());
+        let bob_twin = create_twin(bob());
+        assert_ne!(alice_twin, bob_twin);
+
+        assert_eq!(
+            TwinModule::twins(alice_twin).unwrap().ed25519_account,
+            alice()
+        );
+        assert_eq!(TwinModule::twins(bob_twin).unwrap().ed25519_account, bob());
+        assert_eq!(TwinModule::twin_id_by_account(bob()), Some(bob_twin));
+    });
+}
+
+#[test]
+fn the_farmer_of_a_node_is_the_owner_of_its_farm() {
+    new_test_ext().execute_with(|| {
+        register_node(1);
+
+        assert_eq!(
+            TemplateModule::resolve_farmer(&node_id(1)).ok(),
+            Some(farmer())
+        );
+        // A node that is not registered on chain has no farmer, whatever the explorer says
+        assert!(TemplateModule::resolve_farmer(&node_id(2)).is_err());
+    });
+}
+
+#[test]
+fn a_farm_run_from_a_twins_second_key_pays_the_twin() {
+    new_test_ext().execute_with(|| {
+        // The farmer's twin also answers to bob's key, bob runs the farm
+        assert_ok!(TwinModule::create_twin(
+            Origin::signed(farmer()),
+            Some(bob()),
+            None,
+            vec![]
+        ));
+        assert_ok!(TemplateModule::create_farm(Origin::signed(bob())));
+
+        let pair = node_pair(1);
+        let signature = pair.sign(&(FARM_ID, bob(), 0u64).encode());
+        assert_ok!(TemplateModule::register_node(
+            Origin::signed(bob()),
+            FARM_ID,
+            pair.public(),
+            signature
+        ));
+
+        assert_eq!(
+            TemplateModule::resolve_farmer(&node_id(1)).ok(),
+            Some(farmer())
+        );
+    });
+}
+
+#[test]
+fn capacity_is_held_from_the_first_payment() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        let node_id = node_id(1);
+
+        // An unfunded contract holds nothing on the node
+        assert_eq!(
+            TemplateModule::node_capacity(&node_id).reserved,
+            Resources::default()
+        );
+        assert_eq!(TemplateModule::reserved_capacity(reservation_id), None);
+
+        assert_ok!(TemplateModule::pay(
+            Origin::signed(alice()),
+            reservation_id,
+            PRICE_PER_SEC * 2 * HOUR
+        ));
+        assert_eq!(
+            TemplateModule::node_capacity(&node_id).reserved,
+            volume().get_resources()
+        );
+        assert_eq!(
+            TemplateModule::reserved_capacity(reservation_id),
+            Some(volume().get_resources())
+        );
+
+        // Funding it again does not hold it twice
+        assert_ok!(TemplateModule::pay(
+            Origin::signed(alice()),
+            reservation_id,
+            PRICE_PER_SEC * HOUR
+        ));
+        assert_eq!(
+            TemplateModule::node_capacity(&node_id).reserved,
+            volume().get_resources()
+        );
+
+        assert_ok!(TemplateModule::cancel_contract(
+            Origin::signed(alice()),
+            reservation_id
+        ));
+        assert_eq!(
+            TemplateModule::node_capacity(&node_id).reserved,
+            Resources::default()
+        );
+        assert_eq!(TemplateModule::reserved_capacity(reservation_id), None);
+    });
+}
+
+#[test]
+fn unfunded_contracts_do_not_block_a_node() {
+    new_test_ext().execute_with(|| {
+        let first = setup_contract();
+        let whole_node = VolumeType {
+            disk_type: 1,
+            size: 100,
+        };
+        let second = create_contract_for(alice(), node_id(1), whole_node.clone());
+
+        // Cancelling a contract that never held capacity leaves the node alone
+        assert_ok!(TemplateModule::cancel_contract(
+            Origin::signed(alice()),
+            first
+        ));
+        assert_eq!(
+            TemplateModule::node_capacity(&node_id(1)).reserved,
+            Resources::default()
+        );
+
+        assert_ok!(TemplateModule::pay(
+            Origin::signed(alice()),
+            second,
+            PRICE_PER_SEC * 100 * 2 * HOUR
+        ));
+        assert_eq!(
+            TemplateModule::node_capacity(&node_id(1)).reserved,
+            whole_node.get_resources()
+        );
+
+        // The node is full now
+        let third = create_contract_for(alice(), node_id(1), volume());
+        assert_noop!(
+            TemplateModule::pay(Origin::signed(alice()), third, PRICE_PER_SEC * 2 * HOUR),
+            Error::<Test>::NodeCapacityExceeded
+        );
+        assert_eq!(contract_balance(third), ExistentialDeposit::get());
+    });
+}
+
+#[test]
+fn counter_offer_is_rejected_once_the_contract_is_funded() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        let offered = ResourcePrice {
+            hru: 72,
+            ..ResourcePrice::default()
+        };
+
+        assert_ok!(TemplateModule::counter_offer(
+            Origin::signed(farmer()),
+            reservation_id,
+            offered.clone()
+        ));
+        // The user has to settle on the offer before funding the contract
+        assert_noop!(
+            TemplateModule::pay(
+                Origin::signed(alice()),
+                reservation_id,
+                PRICE_PER_SEC * 2 * HOUR
+            ),
+            Error::<Test>::OfferPending
+        );
+        assert_ok!(TemplateModule::reject_offer(
+            Origin::signed(alice()),
+            reservation_id
+        ));
+
+        assert_ok!(TemplateModule::pay(
+            Origin::signed(alice()),
+            reservation_id,
+            PRICE_PER_SEC * 2 * HOUR
+        ));
+        assert_noop!(
+            TemplateModule::counter_offer(Origin::signed(farmer()), reservation_id, offered),
+            Error::<Test>::ContractAlreadyFunded
+        );
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).resource_prices,
+            prices()
+        );
+    });
+}
+
+#[test]
+fn a_node_without_heartbeats_is_down_from_its_deployment() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 3 * HOUR);
+        deploy(1, reservation_id);
+
+        // The node never sent a heartbeat, only the first interval after deployment is billed
+        set_time(1_000 + 2 * HOUR);
+        let before = Balances::free_balance(&farmer());
+        assert_ok!(TemplateModule::claim_funds(
+            Origin::signed(farmer()),
+            reservation_id
+        ));
+        assert_eq!(
+            Balances::free_balance(&farmer()) - before,
+            PRICE_PER_SEC * HeartbeatInterval::get()
+        );
+    });
+}
+
+#[test]
+fn a_heartbeat_credits_the_downtime_since_deployment() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 3 * HOUR);
+        deploy(1, reservation_id);
+
+        // The first heartbeat comes long after the deployment
+        set_time(1_000 + 3_000);
+        assert_ok!(TemplateModule::heartbeat(
+            Origin::signed(node_account(1)),
+            node_id(1)
+        ));
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).downtime,
+            3_000 - HeartbeatInterval::get()
+        );
+
+        // Heartbeats in time don't add anything
+        set_time(1_000 + 3_000 + HeartbeatInterval::get());
+        assert_ok!(TemplateModule::heartbeat(
+            Origin::signed(node_account(1)),
+            node_id(1)
+        ));
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).downtime,
+            3_000 - HeartbeatInterval::get()
+        );
+    });
+}
+
+#[test]
+fn outages_are_slashed_by_their_length_past_a_grace_period() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 3 * HOUR);
+        deploy(1, reservation_id);
+        let heartbeat = || {
+            assert_ok!(TemplateModule::heartbeat(
+                Origin::signed(node_account(1)),
+                node_id(1)
+            ))
+        };
+        heartbeat();
+
+        let bond = TemplateModule::farm_bond(FARM_ID);
+        let alice_balance = Balances::free_balance(&alice());
+
+        // Down for less than the grace period past the interval
+        set_time(1_000 + HeartbeatInterval::get() + OutageGracePeriod::get());
+        heartbeat();
+        assert_eq!(TemplateModule::farm_bond(FARM_ID), bond);
+
+        // Down for the grace period and half of SlashDowntime
+        let last = 1_000 + HeartbeatInterval::get() + OutageGracePeriod::get();
+        set_time(
+            last + HeartbeatInterval::get() + OutageGracePeriod::get() + SlashDowntime::get() / 2,
+        );
+        heartbeat();
+
+        let slashed = SlashFraction::get() * bond / 2;
+        assert_eq!(TemplateModule::farm_bond(FARM_ID), bond - slashed);
+        assert_eq!(Balances::free_balance(&alice()), alice_balance + slashed);
+    });
+}
+
+#[test]
+fn a_node_holds_a_bounded_number_of_contracts() {
+    new_test_ext().execute_with(|| {
+        let first = setup_contract();
+        fund(first, 2 * HOUR);
+        for _ in 1..MaxContractsPerNode::get() {
+            let reservation_id = create_contract_for(alice(), node_id(1), volume());
+            fund(reservation_id, 2 * HOUR);
+        }
+        assert_eq!(
+            TemplateModule::contracts_for_node(node_id(1)).len() as u32,
+            MaxContractsPerNode::get()
+        );
+
+        let reservation_id = create_contract_for(alice(), node_id(1), volume());
+        assert_noop!(
+            TemplateModule::pay(
+                Origin::signed(alice()),
+                reservation_id,
+                PRICE_PER_SEC * 2 * HOUR
+            ),
+            Error::<Test>::NodeContractLimitReached
+        );
+
+        // Ending a contract makes room again
+        assert_ok!(TemplateModule::cancel_contract(
+            Origin::signed(alice()),
+            first
+        ));
+        fund(reservation_id, 2 * HOUR);
+    });
+}
+
+fn seeds(count: u64) -> Vec<ChallengeSeed> {
+    (0..count)
+        .map(|offset| ChallengeSeed {
+            offset,
+            length: 1,
+            commitment: H256::repeat_byte(offset as u8),
+        })
+        .collect()
+}
+
+// The node's signature over seeds it stores the data for
+fn sign_seeds(seed: u8, reservation_id: u64, seeds: &Vec<ChallengeSeed>) -> ed25519::Signature {
+    let pair = node_pair(seed);
+    let nonce = TemplateModule::node_nonce(&pair.public());
+
+    pair.sign(
+        &(
+            &b"storage_challenge_seeds"[..],
+            reservation_id,
+            seeds,
+            nonce,
+        )
+            .encode(),
+    )
+}
+
+#[test]
+fn storage_challenge_seeds_are_co_signed_by_the_node() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        let seeds = seeds(2);
+
+        // Seeds signed by another key are refused
+        let forged = node_pair(2).sign(
+            &(
+                &b"storage_challenge_seeds"[..],
+                reservation_id,
+                &seeds,
+                0u64,
+            )
+                .encode(),
+        );
+        assert_noop!(
+            TemplateModule::seed_storage_challenges(
+                Origin::signed(alice()),
+                reservation_id,
+                seeds.clone(),
+                forged
+            ),
+            Error::<Test>::InvalidNodeSignature
+        );
+
+        let signature = sign_seeds(1, reservation_id, &seeds);
+        assert_ok!(TemplateModule::seed_storage_challenges(
+            Origin::signed(alice()),
+            reservation_id,
+            seeds.clone(),
+            signature.clone()
+        ));
+        assert_eq!(TemplateModule::challenge_seeds(reservation_id), seeds);
+
+        // The signature can't be replayed
+        assert_noop!(
+            TemplateModule::seed_storage_challenges(
+                Origin::signed(alice()),
+                reservation_id,
+                seeds,
+                signature
+            ),
+            Error::<Test>::InvalidNodeSignature
+        );
+    });
+}
+
+#[test]
+fn storage_challenge_seeds_are_bounded_and_scheduled_once() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+
+        let too_many = seeds(MAX_CHALLENGE_SEEDS as u64 + 1);
+        let signature = sign_seeds(1, reservation_id, &too_many);
+        assert_noop!(
+            TemplateModule::seed_storage_challenges(
+                Origin::signed(alice()),
+                reservation_id,
+                too_many,
+                signature
+            ),
+            Error::<Test>::TooManyChallengeSeeds
+        );
+
+        for _ in 0..2 {
+            let seeds = seeds(1);
+            let signature = sign_seeds(1, reservation_id, &seeds);
+            assert_ok!(TemplateModule::seed_storage_challenges(
+                Origin::signed(alice()),
+                reservation_id,
+                seeds,
+                signature
+            ));
+        }
+
+        let at = System::block_number() + StorageChallengeInterval::get();
+        assert_eq!(
+            TemplateModule::challenge_scheduled_at(reservation_id),
+            Some(at)
+        );
+        assert_eq!(TemplateModule::challenges_at(at), vec![reservation_id]);
+    });
+}
+
+#[test]
+fn contracts_are_claimed_for_the_farm_owner() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+
+        assert_noop!(
+            TemplateModule::set_contract_price(
+                Origin::signed(bob()),
+                reservation_id,
+                prices(),
+                bob()
+            ),
+            Error::<Test>::UnauthorizedFarmer
+        );
+        assert!(TemplateModule::contracts_for_farmer(farmer()).is_empty());
+
+        fund(reservation_id, 2 * HOUR);
+        assert_eq!(
+            TemplateModule::contracts_for_farmer(farmer()),
+            vec![reservation_id]
+        );
+    });
+}
+
+#[test]
+fn claim_all_continues_after_the_last_claimed_contract() {
+    new_test_ext().execute_with(|| {
+        set_time(1_000);
+        create_twin(alice());
+        let node_id = register_node(1);
+        let reservation_ids: Vec<u64> = (0..3)
+            .map(|_| create_contract_for(alice(), node_id.clone(), volume()))
+            .collect();
+        for reservation_id in &reservation_ids {
+            fund(*reservation_id, 2 * HOUR);
+            deploy(1, *reservation_id);
+        }
+
+        set_time(2_000);
+        assert_ok!(TemplateModule::claim_all(Origin::signed(farmer()), None));
+        let claimed = |reservation_id: u64| {
+            TemplateModule::contracts(reservation_id).last_claimed == 2_000_000
+        };
+        assert!(claimed(reservation_ids[0]) && claimed(reservation_ids[1]));
+        assert!(!claimed(reservation_ids[2]));
+
+        // The first contract ending doesn't make the next call skip one
+        assert_ok!(TemplateModule::cancel_contract(
+            Origin::signed(alice()),
+            reservation_ids[0]
+        ));
+        assert_ok!(TemplateModule::claim_all(
+            Origin::signed(farmer()),
+            Some(reservation_ids[1])
+        ));
+        assert!(claimed(reservation_ids[2]));
+    });
+}
+
+#[test]
+fn a_contract_is_reported_deployed_once() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        deploy(1, reservation_id);
+
+        let call = Call::contract_deployed(reservation_id);
+        assert!(!TemplateModule::is_free_report(&node_account(1), &call));
+        assert_noop!(
+            TemplateModule::contract_deployed(Origin::signed(node_account(1)), reservation_id),
+            Error::<Test>::ContractAlreadyDeployed
+        );
+    });
+}
+
+// The node's signature over a deployment result for this chain
+fn sign_result(
+    seed: u8,
+    reservation_id: u64,
+    result: DeploymentResult,
+    genesis_hash: H256,
+) -> ed25519::Signature {
+    let pair = node_pair(seed);
+    let nonce = TemplateModule::node_nonce(&pair.public());
+
+    pair.sign(&(genesis_hash, reservation_id, result, nonce).encode())
+}
+
+#[test]
+fn relayed_results_are_bound_to_the_chain() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        let genesis_hash = System::block_hash(0);
+
+        let other_chain = sign_result(
+            1,
+            reservation_id,
+            DeploymentResult::Deployed,
+            H256::repeat_byte(1),
+        );
+        assert_noop!(
+            TemplateModule::relay_contract_result(
+                Origin::none(),
+                reservation_id,
+                DeploymentResult::Deployed,
+                other_chain
+            ),
+            Error::<Test>::InvalidNodeSignature
+        );
+
+        let signature = sign_result(1, reservation_id, DeploymentResult::Deployed, genesis_hash);
+        assert_ok!(TemplateModule::relay_contract_result(
+            Origin::signed(bob()),
+            reservation_id,
+            DeploymentResult::Deployed,
+            signature
+        ));
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).workload_state,
+            WorkloadState::Deployed
+        );
+    });
+}
+
+fn set_farm_policy(min_prices: ResourcePrice) {
+    assert_ok!(TemplateModule::set_farm_acceptance_policy(
+        Origin::signed(farmer()),
+        FARM_ID,
+        Some(AcceptancePolicy {
+            max_resources: volume().get_resources(),
+            allowed_disk_types: vec![volume().disk_type],
+            min_prepaid_duration: HOUR,
+            min_prices,
+            ..AcceptancePolicy::default()
+        })
+    ));
+}
+
+#[test]
+fn contracts_are_accepted_for_the_farmer_once_paid_its_price() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        set_farm_policy(prices());
+
+        // Pricing alone doesn't accept the contract
+        assert_ok!(TemplateModule::set_contract_price(
+            Origin::signed(bob()),
+            reservation_id,
+            prices(),
+            farmer()
+        ));
+        assert!(!TemplateModule::contracts(reservation_id).accepted);
+
+        fund(reservation_id, 2 * HOUR);
+        assert!(TemplateModule::contracts(reservation_id).accepted);
+    });
+}
+
+#[test]
+fn contracts_below_the_farmers_prices_are_not_accepted() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        set_farm_policy(ResourcePrice {
+            hru: 37,
+            ..ResourcePrice::default()
+        });
+
+        fund(reservation_id, 2 * HOUR);
+        assert!(!TemplateModule::contracts(reservation_id).accepted);
+    });
+}
+
+fn quote(reservation_id: u64) -> Quote {
+    Quote {
+        reservation_id,
+        resource_prices: ResourcePrice {
+            hru: 30,
+            ..ResourcePrice::default()
+        },
+        valid_until: 2_000,
+        lock_duration: HOUR,
+    }
+}
+
+#[test]
+fn quotes_are_signed_by_the_farm_owner() {
+    new_test_ext().execute_with(|| {
+        set_time(1_000);
+        create_twin(alice());
+        let node_id = register_node(1);
+        let reservation_id = ReservationID::get();
+        assert_ok!(TemplateModule::create_contract(
+            Origin::signed(alice()),
+            node_id,
+            volume()
+        ));
+        let quote = quote(reservation_id);
+
+        let forged = sr25519::Pair::from_seed(&[2; 32]).sign(&quote.encode());
+        assert_noop!(
+            TemplateModule::accept_quote(Origin::signed(alice()), quote.clone(), forged),
+            Error::<Test>::InvalidQuoteSignature
+        );
+
+        let signature = farmer_pair().sign(&quote.encode());
+        assert_ok!(TemplateModule::accept_quote(
+            Origin::signed(alice()),
+            quote.clone(),
+            signature
+        ));
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).resource_prices,
+            quote.resource_prices
+        );
+        assert_eq!(TemplateModule::price_locks(reservation_id), 1_000 + HOUR);
+    });
+}
+
+#[test]
+fn price_changes_are_announced_per_node() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        assert_ok!(TemplateModule::set_price_change_policy(
+            Origin::signed(farmer()),
+            FARM_ID,
+            PriceChangePolicy::Notice(100)
+        ));
+
+        assert_noop!(
+            TemplateModule::announce_price_change(Origin::signed(bob()), node_id(1), prices()),
+            Error::<Test>::UnauthorizedFarmer
+        );
+
+        let new_prices = ResourcePrice {
+            hru: 40,
+            ..ResourcePrice::default()
+        };
+        assert_ok!(TemplateModule::announce_price_change(
+            Origin::signed(farmer()),
+            node_id(1),
+            new_prices.clone()
+        ));
+        assert_eq!(
+            TemplateModule::pending_price_changes(reservation_id),
+            Some((new_prices, 1_000 + 100))
+        );
+    });
+}
+
+#[test]
+fn anyone_funds_a_contract_for_its_user_until_it_has_sponsors() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+
+        let amount = PRICE_PER_SEC * 2 * HOUR;
+        assert_ok!(TemplateModule::pay(
+            Origin::signed(charlie()),
+            reservation_id,
+            amount
+        ));
+        assert_eq!(
+            TemplateModule::contributions(reservation_id, alice()),
+            amount
+        );
+        assert_eq!(TemplateModule::contributions(reservation_id, charlie()), 0);
+
+        assert_ok!(TemplateModule::add_sponsor(
+            Origin::signed(alice()),
+            reservation_id,
+            bob(),
+            amount
+        ));
+        assert_noop!(
+            TemplateModule::pay(Origin::signed(charlie()), reservation_id, amount),
+            Error::<Test>::NotASponsor
+        );
+        assert_ok!(TemplateModule::pay(
+            Origin::signed(bob()),
+            reservation_id,
+            amount
+        ));
+        assert_eq!(TemplateModule::contributions(reservation_id, bob()), amount);
+    });
+}
+
+#[test]
+fn the_user_withdraws_its_share_of_the_contract_at_most() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        assert_ok!(TemplateModule::add_sponsor(
+            Origin::signed(alice()),
+            reservation_id,
+            bob(),
+            PRICE_PER_SEC * 2 * HOUR
+        ));
+        assert_ok!(TemplateModule::pay(
+            Origin::signed(bob()),
+            reservation_id,
+            PRICE_PER_SEC * 2 * HOUR
+        ));
+
+        // Half of the balance is bob's
+        assert_noop!(
+            TemplateModule::withdraw(
+                Origin::signed(alice()),
+                reservation_id,
+                PRICE_PER_SEC * 2 * HOUR + 1
+            ),
+            Error::<Test>::InsufficientContractBalance
+        );
+
+        let before = Balances::free_balance(&alice());
+        assert_ok!(TemplateModule::withdraw(
+            Origin::signed(alice()),
+            reservation_id,
+            PRICE_PER_SEC * HOUR
+        ));
+        assert_eq!(
+            Balances::free_balance(&alice()) - before,
+            PRICE_PER_SEC * HOUR
+        );
+        assert_eq!(
+            contract_balance(reservation_id),
+            PRICE_PER_SEC * 3 * HOUR + ExistentialDeposit::get()
+        );
+        assert_eq!(
+            TemplateModule::contributions(reservation_id, alice()),
+            PRICE_PER_SEC * HOUR
+        );
+    });
+}
+
+#[test]
+fn an_organisation_keeps_its_last_admin() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(OrganisationModule::create_organisation(
+            Origin::signed(alice()),
+            b"org".to_vec()
+        ));
+        let org_id = 0;
+
+        assert_ok!(OrganisationModule::set_member(
+            Origin::signed(alice()),
+            org_id,
+            bob(),
+            Role::Admin,
+            None
+        ));
+        assert_eq!(OrganisationModule::admin_count(org_id), 2);
+        assert_ok!(OrganisationModule::set_member(
+            Origin::signed(bob()),
+            org_id,
+            alice(),
+            Role::Viewer,
+            None
+        ));
+        assert_eq!(OrganisationModule::admin_count(org_id), 1);
+
+        assert_noop!(
+            OrganisationModule::set_member(
+                Origin::signed(bob()),
+                org_id,
+                bob(),
+                Role::Deployer,
+                None
+            ),
+            pallet_organisation::Error::<Test>::LastAdmin
+        );
+        assert_noop!(
+            OrganisationModule::remove_member(Origin::signed(bob()), org_id, bob()),
+            pallet_organisation::Error::<Test>::LastAdmin
+        );
+        assert_ok!(OrganisationModule::remove_member(
+            Origin::signed(bob()),
+            org_id,
+            alice()
+        ));
+        assert_eq!(OrganisationModule::admin_count(org_id), 1);
+    });
+}
+
+#[test]
+fn only_an_admin_transfers_an_organisation_contract() {
+    new_test_ext().execute_with(|| {
+        set_time(1_000);
+        create_twin(alice());
+        create_twin(charlie());
+        let node_id = register_node(1);
+        assert_ok!(OrganisationModule::create_organisation(
+            Origin::signed(alice()),
+            b"org".to_vec()
+        ));
+        let org_id = 0;
+        assert_ok!(OrganisationModule::set_member(
+            Origin::signed(alice()),
+            org_id,
+            bob(),
+            Role::Deployer,
+            None
+        ));
+
+        let reservation_id = ReservationID::get();
+        assert_ok!(TemplateModule::create_org_contract(
+            Origin::signed(alice()),
+            org_id,
+            node_id,
+            volume()
+        ));
+
+        assert_noop!(
+            TemplateModule::offer_contract_transfer(Origin::signed(bob()), reservation_id, bob()),
+            pallet_organisation::Error::<Test>::UnauthorizedMember
+        );
+
+        assert_ok!(TemplateModule::offer_contract_transfer(
+            Origin::signed(alice()),
+            reservation_id,
+            charlie()
+        ));
+        assert_noop!(
+            TemplateModule::cancel_contract_transfer(Origin::signed(bob()), reservation_id),
+            pallet_organisation::Error::<Test>::UnauthorizedMember
+        );
+        assert_ok!(TemplateModule::accept_contract_transfer(
+            Origin::signed(charlie()),
+            reservation_id
+        ));
+
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).user_account,
+            charlie()
+        );
+        assert_eq!(TemplateModule::contract_org(reservation_id), None);
+        assert!(TemplateModule::reservations_for_org(org_id).is_empty());
+    });
+}
+
+#[test]
+fn organisation_contracts_are_managed_by_deployers_within_their_limit() {
+    new_test_ext().execute_with(|| {
+        set_time(1_000);
+        create_twin(alice());
+        let node_id = register_node(1);
+        assert_ok!(OrganisationModule::create_organisation(
+            Origin::signed(alice()),
+            b"org".to_vec()
+        ));
+        let org_id = 0;
+        let limit = PRICE_PER_SEC * 2 * HOUR;
+        assert_ok!(OrganisationModule::set_member(
+            Origin::signed(alice()),
+            org_id,
+            bob(),
+            Role::Deployer,
+            Some(limit)
+        ));
+
+        let reservation_id = ReservationID::get();
+        assert_ok!(TemplateModule::create_org_contract(
+            Origin::signed(alice()),
+            org_id,
+            node_id,
+            volume()
+        ));
+
+        // A deployer manages the contract like its user would, anyone else can't
+        assert_ok!(TemplateModule::add_sponsor(
+            Origin::signed(bob()),
+            reservation_id,
+            charlie(),
+            PRICE_PER_SEC
+        ));
+        assert!(TemplateModule::remove_sponsor(
+            Origin::signed(charlie()),
+            reservation_id,
+            charlie()
+        )
+        .is_err());
+        assert_ok!(TemplateModule::remove_sponsor(
+            Origin::signed(bob()),
+            reservation_id,
+            charlie()
+        ));
+
+        // Funding it outside of pay_from_org would dodge the spending limits
+        assert_noop!(
+            TemplateModule::pay(
+                Origin::signed(alice()),
+                reservation_id,
+                PRICE_PER_SEC * HOUR
+            ),
+            Error::<Test>::OrgContractPayment
+        );
+
+        // An auto top-up draws on the organisation, its cap counts against the deployer's limit
+        assert_noop!(
+            TemplateModule::set_auto_top_up(
+                Origin::signed(bob()),
+                reservation_id,
+                HOUR,
+                PRICE_PER_SEC * HOUR,
+                limit + 1
+            ),
+            pallet_organisation::Error::<Test>::SpendingLimitExceeded
+        );
+        assert_ok!(TemplateModule::set_auto_top_up(
+            Origin::signed(bob()),
+            reservation_id,
+            HOUR,
+            PRICE_PER_SEC * HOUR,
+            PRICE_PER_SEC * HOUR
+        ));
+        assert_eq!(
+            OrganisationModule::members(org_id, bob()).unwrap().spent,
+            PRICE_PER_SEC * HOUR
+        );
+
+        // Raising the cap only counts what it grew by
+        assert_ok!(TemplateModule::set_auto_top_up(
+            Origin::signed(bob()),
+            reservation_id,
+            HOUR,
+            PRICE_PER_SEC * HOUR,
+            limit
+        ));
+        assert_eq!(
+            OrganisationModule::members(org_id, bob()).unwrap().spent,
+            limit
+        );
+        assert_noop!(
+            TemplateModule::pay_from_org(Origin::signed(bob()), reservation_id, PRICE_PER_SEC),
+            pallet_organisation::Error::<Test>::SpendingLimitExceeded
+        );
+    });
+}
+
+// A group of alice's, priced but not funded
+fn create_group(workloads: Vec<(Vec<u8>, VolumeType)>) -> (u64, Vec<u64>) {
+    let group_id = GroupID::get();
+    let first = ReservationID::get();
+    let count = workloads.len() as u64;
+    assert_ok!(TemplateModule::create_group(
+        Origin::signed(alice()),
+        workloads
+    ));
+
+    let reservation_ids: Vec<u64> = (first..first + count).collect();
+    for reservation_id in &reservation_ids {
+        assert_ok!(TemplateModule::set_contract_price(
+            Origin::signed(alice()),
+            *reservation_id,
+            prices(),
+            farmer()
+        ));
+    }
+
+    (group_id, reservation_ids)
+}
+
+#[test]
+fn a_group_is_funded_entirely_or_not_at_all() {
+    new_test_ext().execute_with(|| {
+        set_time(1_000);
+        create_twin(alice());
+        let node_id = register_node(1);
+        // Each volume fits on the node, both together don't
+        let large = VolumeType {
+            disk_type: 1,
+            size: 60,
+        };
+        let (group_id, _) = create_group(vec![(node_id.clone(), large.clone()), (node_id, large)]);
+
+        assert_noop!(
+            TemplateModule::fund_group(
+                Origin::signed(alice()),
+                group_id,
+                2 * 60 * PRICE_PER_SEC * 2 * HOUR
+            ),
+            Error::<Test>::NodeCapacityExceeded
+        );
+    });
+}
+
+#[test]
+fn a_group_runs_its_prepaid_time_from_its_activation() {
+    new_test_ext().execute_with(|| {
+        set_time(1_000);
+        create_twin(alice());
+        let first = register_node(1);
+        let second = register_node(2);
+        let (group_id, reservation_ids) = create_group(vec![(first, volume()), (second, volume())]);
+        assert_ok!(TemplateModule::fund_group(
+            Origin::signed(alice()),
+            group_id,
+            2 * PRICE_PER_SEC * 2 * HOUR
+        ));
+
+        deploy(1, reservation_ids[0]);
+        set_time(1_500);
+        deploy(2, reservation_ids[1]);
+
+        for reservation_id in reservation_ids {
+            assert_eq!(
+                TemplateModule::contracts(reservation_id).expires_at,
+                1_500 + 2 * HOUR
+            );
+        }
+    });
+}
+
+#[test]
+fn a_cancelled_contract_is_not_rejected() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        assert_ok!(TemplateModule::cancel_contract(
+            Origin::signed(alice()),
+            reservation_id
+        ));
+
+        assert_noop!(
+            TemplateModule::reject_contract(Origin::signed(farmer()), reservation_id),
+            Error::<Test>::ContractCancelled
+        );
+    });
+}
+
+#[test]
+fn a_cancelled_contract_refunds_its_payers_by_their_contributions() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        let amount = PRICE_PER_SEC * 2 * HOUR;
+        assert_ok!(TemplateModule::add_sponsor(
+            Origin::signed(alice()),
+            reservation_id,
+            bob(),
+            amount
+        ));
+        fund(reservation_id, 2 * HOUR);
+        assert_ok!(TemplateModule::pay(
+            Origin::signed(bob()),
+            reservation_id,
+            amount
+        ));
+
+        let alice_before = Balances::free_balance(&alice());
+        let bob_before = Balances::free_balance(&bob());
+        assert_ok!(TemplateModule::cancel_contract(
+            Origin::signed(alice()),
+            reservation_id
+        ));
+
+        assert_eq!(Balances::free_balance(&alice()) - alice_before, amount);
+        assert_eq!(Balances::free_balance(&bob()) - bob_before, amount);
+        assert_eq!(contract_balance(reservation_id), 0);
+    });
+}
+
+#[test]
+fn rejecting_a_contract_rolls_its_group_back() {
+    new_test_ext().execute_with(|| {
+        set_time(1_000);
+        create_twin(alice());
+        let first = register_node(1);
+        let second = register_node(2);
+        let (group_id, reservation_ids) = create_group(vec![(first, volume()), (second, volume())]);
+
+        let before = Balances::free_balance(&alice());
+        assert_ok!(TemplateModule::fund_group(
+            Origin::signed(alice()),
+            group_id,
+            2 * PRICE_PER_SEC * 2 * HOUR
+        ));
+        deploy(2, reservation_ids[1]);
+
+        assert_ok!(TemplateModule::reject_contract(
+            Origin::signed(farmer()),
+            reservation_ids[0]
+        ));
+
+        assert_eq!(Balances::free_balance(&alice()), before);
+        for reservation_id in reservation_ids {
+            assert_eq!(
+                TemplateModule::contracts(reservation_id).workload_state,
+                WorkloadState::Cancelled
+            );
+            assert_eq!(TemplateModule::reserved_capacity(reservation_id), None);
+        }
+    });
+}
+
+// Finalize a block at `secs`, the pallet handles the seconds since the previous one
+fn finalize_at(block: u64, secs: u64) {
+    System::set_block_number(block);
+    set_time(secs);
+    TemplateModule::on_finalize(block);
+}
+
+#[test]
+fn a_contract_running_low_is_topped_up_from_its_user() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        let expires_at = TemplateModule::contracts(reservation_id).expires_at;
+        let amount = PRICE_PER_SEC * HOUR;
+        assert_ok!(TemplateModule::set_auto_top_up(
+            Origin::signed(alice()),
+            reservation_id,
+            HOUR,
+            amount,
+            amount
+        ));
+
+        finalize_at(1, 1_000);
+        let before = Balances::free_balance(&alice());
+        finalize_at(2, expires_at - HOUR + 1);
+
+        assert_eq!(before - Balances::free_balance(&alice()), amount);
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).expires_at,
+            expires_at + HOUR
+        );
+
+        // The cap is spent, the rule is dropped at the next check
+        finalize_at(3, expires_at + 1);
+        assert_eq!(Balances::free_balance(&alice()), before - amount);
+        assert!(TemplateModule::top_up_rules(reservation_id).is_none());
+    });
+}
+
+fn heartbeat_at(seed: u8, secs: u64) {
+    set_time(secs);
+    assert_ok!(TemplateModule::heartbeat(
+        Origin::signed(node_account(seed)),
+        node_id(seed)
+    ));
+}
+
+#[test]
+fn earnings_go_to_a_new_payout_address_once_it_takes_effect() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 3 * HOUR);
+        deploy(1, reservation_id);
+        assert_ok!(TemplateModule::set_payout_address(
+            Origin::signed(farmer()),
+            charlie()
+        ));
+
+        heartbeat_at(1, 1_500);
+        heartbeat_at(1, 2_000);
+        let farmer_before = Balances::free_balance(&farmer());
+        assert_ok!(TemplateModule::claim_funds(
+            Origin::signed(farmer()),
+            reservation_id
+        ));
+        assert_eq!(
+            Balances::free_balance(&farmer()) - farmer_before,
+            PRICE_PER_SEC * 1_000
+        );
+
+        System::set_block_number(1 + PayoutChangeDelay::get());
+        heartbeat_at(1, 2_400);
+        let charlie_before = Balances::free_balance(&charlie());
+        assert_ok!(TemplateModule::claim_funds(
+            Origin::signed(farmer()),
+            reservation_id
+        ));
+        assert_eq!(
+            Balances::free_balance(&charlie()) - charlie_before,
+            PRICE_PER_SEC * 400
+        );
+        assert_eq!(TemplateModule::payout_account(&farmer()), charlie());
+    });
+}
+
+#[test]
+fn a_dispute_freezes_claims_until_the_earnings_are_split() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 3 * HOUR);
+        deploy(1, reservation_id);
+        heartbeat_at(1, 1_500);
+        heartbeat_at(1, 2_000);
+
+        assert_ok!(TemplateModule::open_dispute(
+            Origin::signed(alice()),
+            reservation_id,
+            b"not running".to_vec()
+        ));
+        assert_noop!(
+            TemplateModule::claim_funds(Origin::signed(farmer()), reservation_id),
+            Error::<Test>::ContractDisputed
+        );
+
+        let alice_before = Balances::free_balance(&alice());
+        let farmer_before = Balances::free_balance(&farmer());
+        assert_ok!(TemplateModule::resolve_dispute(
+            Origin::root(),
+            reservation_id,
+            Perbill::from_percent(50)
+        ));
+
+        assert_eq!(
+            Balances::free_balance(&alice()) - alice_before,
+            PRICE_PER_SEC * 500
+        );
+        assert_eq!(
+            Balances::free_balance(&farmer()) - farmer_before,
+            PRICE_PER_SEC * 500
+        );
+        assert!(TemplateModule::disputes(reservation_id).is_none());
+    });
+}
+
+#[test]
+fn a_withdrawal_leaves_the_minimum_runway() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 3 * HOUR);
+
+        // A second less than the minimum would be left
+        assert_noop!(
+            TemplateModule::withdraw(
+                Origin::signed(alice()),
+                reservation_id,
+                PRICE_PER_SEC * (2 * HOUR + 1)
+            ),
+            Error::<Test>::InsufficientPrepayment
+        );
+        assert_ok!(TemplateModule::withdraw(
+            Origin::signed(alice()),
+            reservation_id,
+            PRICE_PER_SEC * HOUR
+        ));
+        assert_eq!(
+            contract_balance(reservation_id),
+            PRICE_PER_SEC * 2 * HOUR + ExistentialDeposit::get()
+        );
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).expires_at,
+            1_000 + 2 * HOUR
+        );
+    });
+}
+
+#[test]
+fn refilling_a_contract_runs_its_whole_balance_from_now_or_its_last_claim() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).expires_at,
+            1_000 + 2 * HOUR
+        );
+
+        // Nothing was billed before deployment, the whole balance runs from now
+        set_time(2_000);
+        fund(reservation_id, HOUR);
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).expires_at,
+            2_000 + 3 * HOUR
+        );
+        assert_eq!(
+            TemplateModule::contracts_per_expiration(2_000 + 3 * HOUR),
+            vec![reservation_id]
+        );
+        assert!(TemplateModule::contracts_per_expiration(1_000 + 2 * HOUR).is_empty());
+
+        // Once deployed, the balance pays from the last claim on
+        deploy(1, reservation_id);
+        set_time(2_000 + HOUR);
+        fund(reservation_id, HOUR);
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).expires_at,
+            2_000 + 4 * HOUR
+        );
+    });
+}
+
+// Contracts of alice's on a fresh node, funded for two hours and deployed at 1_000
+fn deploy_contracts(count: u64) -> Vec<u64> {
+    set_time(1_000);
+    create_twin(alice());
+    let node_id = register_node(1);
+
+    (0..count)
+        .map(|_| {
+            let reservation_id = create_contract_for(alice(), node_id.clone(), volume());
+            fund(reservation_id, 2 * HOUR);
+            deploy(1, reservation_id);
+            reservation_id
+        })
+        .collect()
+}
+
+#[test]
+fn claim_all_checks_the_existential_deposit_on_the_whole_payout() {
+    new_test_ext().execute_with(|| {
+        set_existential_deposit(PRICE_PER_SEC * 1_500);
+        let reservation_ids = deploy_contracts(2);
+
+        // A payout address without an account yet
+        let payout = account(4);
+        assert_ok!(TemplateModule::set_payout_address(
+            Origin::signed(farmer()),
+            payout
+        ));
+        System::set_block_number(1 + PayoutChangeDelay::get());
+
+        set_time(1_500);
+        assert_noop!(
+            TemplateModule::claim_all(Origin::signed(farmer()), None),
+            Error::<Test>::PayoutBelowExistentialDeposit
+        );
+
+        // Each contract owes less than the existential deposit, together they owe more
+        set_time(2_000);
+        assert_ok!(TemplateModule::claim_all(Origin::signed(farmer()), None));
+        assert_eq!(Balances::free_balance(&payout), PRICE_PER_SEC * 2_000);
+        for reservation_id in reservation_ids {
+            assert_eq!(
+                TemplateModule::contracts(reservation_id).last_claimed,
+                2_000_000
+            );
+        }
+    });
+}
+
+#[test]
+fn claim_all_skips_a_disputed_contract() {
+    new_test_ext().execute_with(|| {
+        let reservation_ids = deploy_contracts(2);
+        assert_ok!(TemplateModule::open_dispute(
+            Origin::signed(alice()),
+            reservation_ids[0],
+            b"evidence".to_vec()
+        ));
+
+        set_time(2_000);
+        let before = Balances::free_balance(&farmer());
+        assert_ok!(TemplateModule::claim_all(Origin::signed(farmer()), None));
+
+        assert_eq!(
+            TemplateModule::contracts(reservation_ids[0]).last_claimed,
+            1_000_000
+        );
+        assert_eq!(
+            TemplateModule::contracts(reservation_ids[1]).last_claimed,
+            2_000_000
+        );
+        assert_eq!(
+            Balances::free_balance(&farmer()),
+            before + PRICE_PER_SEC * 1_000
+        );
+    });
+}
+
+fn sized(size: u64) -> VolumeType {
+    VolumeType { disk_type: 1, size }
+}
+
+#[test]
+fn an_update_is_applied_once_the_farmer_accepted_and_the_node_confirmed_it() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        deploy(1, reservation_id);
+
+        assert_noop!(
+            TemplateModule::update_contract(Origin::signed(bob()), reservation_id, sized(2)),
+            Error::<Test>::UnauthorizedUser
+        );
+        assert_ok!(TemplateModule::update_contract(
+            Origin::signed(alice()),
+            reservation_id,
+            sized(2)
+        ));
+        assert_noop!(
+            TemplateModule::update_contract(Origin::signed(alice()), reservation_id, sized(3)),
+            Error::<Test>::UpdatePending
+        );
+
+        // The node can't apply an update the farmer didn't accept
+        assert_noop!(
+            TemplateModule::contract_updated(Origin::signed(node_account(1)), reservation_id),
+            Error::<Test>::UpdateNotAccepted
+        );
+        assert_noop!(
+            TemplateModule::accept_contract_update(Origin::signed(alice()), reservation_id),
+            Error::<Test>::UnauthorizedFarmer
+        );
+        assert_ok!(TemplateModule::accept_contract_update(
+            Origin::signed(farmer()),
+            reservation_id
+        ));
+        assert_noop!(
+            TemplateModule::contract_updated(Origin::signed(bob()), reservation_id),
+            Error::<Test>::UnauthorizedNode
+        );
+
+        set_time(2_000);
+        assert_ok!(TemplateModule::contract_updated(
+            Origin::signed(node_account(1)),
+            reservation_id
+        ));
+        assert_eq!(TemplateModule::pending_updates(reservation_id), None);
+        assert_eq!(
+            TemplateModule::volume_reservations(reservation_id),
+            sized(2)
+        );
+        assert_eq!(
+            TemplateModule::reserved_capacity(reservation_id),
+            Some(sized(2).get_resources())
+        );
+        assert_eq!(
+            TemplateModule::node_capacity(&node_id(1)).reserved,
+            sized(2).get_resources()
+        );
+
+        // The old workload was billed up to the switch, what is left runs at twice the price
+        let contract = TemplateModule::contracts(reservation_id);
+        assert_eq!(contract.last_claimed, 2_000_000);
+        assert_eq!(
+            contract_balance(reservation_id),
+            PRICE_PER_SEC * (2 * HOUR - 1_000) + ExistentialDeposit::get()
+        );
+        assert_eq!(contract.expires_at, 2_000 + (2 * HOUR - 1_000) / 2);
+    });
+}
+
+#[test]
+fn an_update_is_withdrawn_by_the_user_or_rejected_by_the_farmer() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        deploy(1, reservation_id);
+
+        assert_noop!(
+            TemplateModule::cancel_contract_update(Origin::signed(alice()), reservation_id),
+            Error::<Test>::UpdateNotExists
+        );
+
+        assert_ok!(TemplateModule::update_contract(
+            Origin::signed(alice()),
+            reservation_id,
+            sized(2)
+        ));
+        assert_noop!(
+            TemplateModule::cancel_contract_update(Origin::signed(bob()), reservation_id),
+            Error::<Test>::UnauthorizedUser
+        );
+        assert_ok!(TemplateModule::cancel_contract_update(
+            Origin::signed(alice()),
+            reservation_id
+        ));
+        assert_eq!(TemplateModule::pending_updates(reservation_id), None);
+
+        assert_ok!(TemplateModule::update_contract(
+            Origin::signed(alice()),
+            reservation_id,
+            sized(2)
+        ));
+        assert_ok!(TemplateModule::cancel_contract_update(
+            Origin::signed(farmer()),
+            reservation_id
+        ));
+        assert_eq!(TemplateModule::pending_updates(reservation_id), None);
+        assert_noop!(
+            TemplateModule::contract_updated(Origin::signed(node_account(1)), reservation_id),
+            Error::<Test>::UpdateNotExists
+        );
+        assert_eq!(
+            TemplateModule::volume_reservations(reservation_id),
+            volume()
+        );
+    });
+}
+
+#[test]
+fn an_update_has_to_fit_on_the_node() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        deploy(1, reservation_id);
+
+        // Another contract holds part of the node's disks
+        let other = create_contract_for(alice(), node_id(1), volume());
+        fund(other, 2 * HOUR);
+
+        // The whole node would only fit if the contract's own share was the only one held
+        assert_ok!(TemplateModule::update_contract(
+            Origin::signed(alice()),
+            reservation_id,
+            sized(100)
+        ));
+        assert_ok!(TemplateModule::accept_contract_update(
+            Origin::signed(farmer()),
+            reservation_id
+        ));
+        assert_noop!(
+            TemplateModule::contract_updated(Origin::signed(node_account(1)), reservation_id),
+            Error::<Test>::NodeCapacityExceeded
+        );
+
+        // Growing into what is left is fine
+        assert_ok!(TemplateModule::cancel_contract_update(
+            Origin::signed(alice()),
+            reservation_id
+        ));
+        assert_ok!(TemplateModule::update_contract(
+            Origin::signed(alice()),
+            reservation_id,
+            sized(99)
+        ));
+        assert_ok!(TemplateModule::accept_contract_update(
+            Origin::signed(farmer()),
+            reservation_id
+        ));
+        assert_ok!(TemplateModule::contract_updated(
+            Origin::signed(node_account(1)),
+            reservation_id
+        ));
+        assert_eq!(
+            TemplateModule::node_capacity(&node_id(1)).reserved,
+            sized(100).get_resources()
+        );
+    });
+}
+
+#[test]
+fn a_delegate_only_acts_within_its_scopes() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        deploy(1, reservation_id);
+
+        assert_ok!(TemplateModule::add_delegate(
+            Origin::signed(farmer()),
+            bob(),
+            vec![DelegateScope::ClaimFunds],
+            10
+        ));
+
+        // Accepting contracts was not delegated
+        assert_noop!(
+            TemplateModule::accept_contract(Origin::signed(bob()), reservation_id),
+            Error::<Test>::UnauthorizedFarmer
+        );
+        // Nor was anything delegated to charlie
+        set_time(2_000);
+        assert_noop!(
+            TemplateModule::claim_funds(Origin::signed(charlie()), reservation_id),
+            Error::<Test>::UnauthorizedFarmer
+        );
+
+        // The earnings still go to the farmer
+        let before = Balances::free_balance(&farmer());
+        assert_ok!(TemplateModule::claim_funds(
+            Origin::signed(bob()),
+            reservation_id
+        ));
+        assert_eq!(
+            Balances::free_balance(&farmer()),
+            before + PRICE_PER_SEC * 1_000
+        );
+    });
+}
+
+#[test]
+fn an_expired_delegate_is_rejected() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        deploy(1, reservation_id);
+
+        // A delegation has to outlive the current block
+        assert_noop!(
+            TemplateModule::add_delegate(
+                Origin::signed(farmer()),
+                bob(),
+                vec![DelegateScope::ClaimFunds],
+                1
+            ),
+            Error::<Test>::DelegationExpired
+        );
+        assert_ok!(TemplateModule::add_delegate(
+            Origin::signed(farmer()),
+            bob(),
+            vec![DelegateScope::ClaimFunds],
+            10
+        ));
+
+        System::set_block_number(10);
+        set_time(2_000);
+        assert_noop!(
+            TemplateModule::claim_funds(Origin::signed(bob()), reservation_id),
+            Error::<Test>::UnauthorizedFarmer
+        );
+
+        // The farmer itself isn't bound by the delegation
+        assert_ok!(TemplateModule::claim_funds(
+            Origin::signed(farmer()),
+            reservation_id
+        ));
+    });
+}
+
+#[test]
+fn a_zero_payout_period_turns_automatic_payouts_off() {
+    new_test_ext().execute_with(|| {
+        let reservation_id = setup_contract();
+        fund(reservation_id, 2 * HOUR);
+        deploy(1, reservation_id);
+        assert_ok!(TemplateModule::set_auto_payout(
+            Origin::signed(farmer()),
+            true
+        ));
+
+        set_auto_payout_period(0);
+        set_time(2_000);
+        for n in 1..=20 {
+            TemplateModule::on_initialize(n);
+        }
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).last_claimed,
+            1_000_000
+        );
+
+        // The same blocks start a sweep once there is a period again
+        set_auto_payout_period(10);
+        TemplateModule::on_initialize(20);
+        assert_eq!(
+            TemplateModule::contracts(reservation_id).last_claimed,
+            2_000_000
+        );
+    });
+}
+
+#[test]
+fn capacity_reservation_fits() {
+    let capacity = Capacity {
+        total: Resources {
+            cru: 4,
+            mru: 16,
+            sru: 100,
+            hru: 1000,
+        },
+        reserved: Resources {
+            sru: 60,
+            ..Resources::default()
+        },
+    };
+
+    let small = VolumeType {
+        disk_type: 2,
+        size: 40,
+    };
+    let large = VolumeType {
+        disk_type: 2,
+        size: 41,
+    };
+
+    assert!(small.get_resources().fits_in(&capacity.free()));
+    assert!(!large.get_resources().fits_in(&capacity.free()));
+    assert_eq!(capacity.free().sub(&small.get_resources()).sru, 0);
+}
+
+#[test]
+fn number_to_str_bytes() {
+    let case1 = (3242, "3242");
+    let case2 = (002342, "2342");
+    let case3 = (1234567890, "1234567890");
+
+    assert_eq!(case1.1, str::from_utf8(&to_str_bytes(case1.0)).unwrap());
+    assert_eq!(case2.1, str::from_utf8(&to_str_bytes(case2.0)).unwrap());
+    assert_eq!(case3.1, str::from_utf8(&to_str_bytes(case3.0)).unwrap());
+}
@@ -0,0 +1,156 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! On-chain identities ("twins") for the users and farmers of the grid.
+//! A twin gets its id from the chain when it is created and replaces the explorer phonebook.
+//! A farm's owner needs a twin: its farmer is paid on the twin's ed25519 account, whichever of the
+//! twin's keys runs the farm.
+
+use codec::{Decode, Encode};
+use frame_support::{
+    debug, decl_error, decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure,
+    traits::Get,
+};
+use frame_system::{self as system, ensure_signed};
+use sp_runtime::RuntimeDebug;
+use sp_std::prelude::*;
+
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Default, RuntimeDebug)]
+pub struct Twin<AccountId> {
+    // Account derived from the 3bot's ed25519 key, the one farmers get paid on
+    pub ed25519_account: AccountId,
+    pub sr25519_account: Option<AccountId>,
+    pub name: Option<Vec<u8>>,
+    pub endpoints: Vec<Vec<u8>>,
+}
+
+impl<AccountId: PartialEq> Twin<AccountId> {
+    pub fn is_owned_by(&self, who: &AccountId) -> bool {
+        &self.ed25519_account == who || self.sr25519_account.as_ref() == Some(who)
+    }
+}
+
+pub trait Trait: system::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as TwinModule {
+        pub Twins get(fn twins): map hasher(blake2_128_concat) u64 => Option<Twin<T::AccountId>>;
+        pub TwinIdByAccount get(fn twin_id_by_account): map hasher(blake2_128_concat) T::AccountId => Option<u64>;
+        TwinID: u64;
+    }
+}
+
+decl_event!(
+    pub enum Event<T>
+    where
+        AccountId = <T as frame_system::Trait>::AccountId,
+    {
+        TwinCreated(u64, AccountId),
+        TwinUpdated(u64),
+        TwinDeleted(u64),
+    }
+);
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        TwinExists,
+        TwinNotExists,
+        AccountHasTwin,
+        UnauthorizedTwin,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        // Ids are handed out by the chain, so nobody can claim the id of someone else's 3bot
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(4,4)]
+        pub fn create_twin(origin, sr25519_account: Option<T::AccountId>, name: Option<Vec<u8>>, endpoints: Vec<Vec<u8>>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let twin_id = TwinID::get();
+            ensure!(!Twins::<T>::contains_key(twin_id), Error::<T>::TwinExists);
+            ensure!(!TwinIdByAccount::<T>::contains_key(&who), Error::<T>::AccountHasTwin);
+            if let Some(account) = &sr25519_account {
+                ensure!(!TwinIdByAccount::<T>::contains_key(account), Error::<T>::AccountHasTwin);
+            }
+
+            let twin = Twin {
+                ed25519_account: who.clone(),
+                sr25519_account,
+                name,
+                endpoints,
+            };
+            debug::info!("Creating twin: {:?} with id: {:?}", twin, twin_id);
+
+            TwinIdByAccount::<T>::insert(&who, twin_id);
+            if let Some(account) = &twin.sr25519_account {
+                TwinIdByAccount::<T>::insert(account, twin_id);
+            }
+            Twins::<T>::insert(twin_id, &twin);
+            TwinID::put(twin_id + 1);
+
+            Self::deposit_event(RawEvent::TwinCreated(twin_id, who));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(2,3)]
+        pub fn update_twin(origin, twin_id: u64, sr25519_account: Option<T::AccountId>, name: Option<Vec<u8>>, endpoints: Vec<Vec<u8>>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let mut twin = Twins::<T>::get(twin_id).ok_or(Error::<T>::TwinNotExists)?;
+            ensure!(twin.is_owned_by(&who), Error::<T>::UnauthorizedTwin);
+
+            if twin.sr25519_account != sr25519_account {
+                if let Some(account) = &sr25519_account {
+                    ensure!(!TwinIdByAccount::<T>::contains_key(account), Error::<T>::AccountHasTwin);
+                    TwinIdByAccount::<T>::insert(account, twin_id);
+                }
+                if let Some(account) = &twin.sr25519_account {
+                    TwinIdByAccount::<T>::remove(account);
+                }
+            }
+
+            twin.sr25519_account = sr25519_account;
+            twin.name = name;
+            twin.endpoints = endpoints;
+
+            Twins::<T>::insert(twin_id, &twin);
+
+            Self::deposit_event(RawEvent::TwinUpdated(twin_id));
+
+            Ok(())
+        }
+
+        #[weight = 10_000 + T::DbWeight::get().reads_writes(1,3)]
+        pub fn delete_twin(origin, twin_id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let twin = Twins::<T>::get(twin_id).ok_or(Error::<T>::TwinNotExists)?;
+            ensure!(twin.is_owned_by(&who), Error::<T>::UnauthorizedTwin);
+
+            TwinIdByAccount::<T>::remove(&twin.ed25519_account);
+            if let Some(account) = &twin.sr25519_account {
+                TwinIdByAccount::<T>::remove(account);
+            }
+            Twins::<T>::remove(twin_id);
+
+            Self::deposit_event(RawEvent::TwinDeleted(twin_id));
+
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Resolve the twin an account belongs to, through either of its keys.
+    pub fn twin_of(who: &T::AccountId) -> Option<(u64, Twin<T::AccountId>)> {
+        let twin_id = TwinIdByAccount::<T>::get(who)?;
+        Twins::<T>::get(twin_id).map(|twin| (twin_id, twin))
+    }
+}
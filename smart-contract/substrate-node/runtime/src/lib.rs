@@ -41,6 +41,18 @@ pub use frame_support::{
 /// Import the template pallet.
 pub use pallet_template;
 
+/// Import the twin pallet.
+pub use pallet_twin;
+
+/// Import the DID pallet.
+pub use pallet_did;
+
+/// Import the organisation pallet.
+pub use pallet_organisation;
+
+mod node_reports;
+pub use node_reports::ChargeNodeReportFee;
+
 /// An index to a block.
 pub type BlockNumber = u32;
 
@@ -273,7 +285,7 @@ where
 			frame_system::CheckEra::<Runtime>::from(generic::Era::mortal(period, current_block)),
 			frame_system::CheckNonce::<Runtime>::from(index),
 			frame_system::CheckWeight::<Runtime>::new(),
-			pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(tip),
+			ChargeNodeReportFee::from(tip),
 		);
 
 		#[cfg_attr(not(feature = "std"), allow(unused_variables))]
@@ -321,12 +333,77 @@ impl pallet_sudo::Trait for Runtime {
 	type Call = Call;
 }
 
+/// Configure the twin pallet in pallets/twin.
+impl pallet_twin::Trait for Runtime {
+	type Event = Event;
+}
+
+/// Configure the DID pallet in pallets/did.
+impl pallet_did::Trait for Runtime {
+	type Event = Event;
+}
+
+/// Configure the organisation pallet in pallets/organisation.
+impl pallet_organisation::Trait for Runtime {
+	type Event = Event;
+	type Currency = Balances;
+}
+
+parameter_types! {
+	/// Nodes are expected to send a heartbeat at least every 10 minutes.
+	pub const HeartbeatInterval: u64 = 10 * 60;
+	/// Farmers bond 0.01 token for every core or GB of memory and disk they register.
+	pub const BondPerUnit: Balance = 10_000_000_000;
+	pub const SlashFraction: Perbill = Perbill::from_percent(10);
+	/// Outages are slashed after 30 minutes, a day of downtime costs the whole SlashFraction.
+	pub const OutageGracePeriod: u64 = 30 * 60;
+	pub const SlashDowntime: u64 = 24 * 60 * 60;
+	/// Users can dispute a deployment up to a day after it was reported.
+	pub const ChallengeWindow: u64 = 24 * 60 * 60;
+	/// Volumes are challenged every hour and nodes get 10 minutes to answer.
+	pub const StorageChallengeInterval: BlockNumber = HOURS;
+	pub const StorageChallengeTimeout: BlockNumber = 10 * MINUTES;
+	/// Farmers with automatic payouts get paid once a day, 50 contracts per block.
+	pub const AutoPayoutPeriod: BlockNumber = DAYS;
+	pub const MaxPayoutsPerBlock: u32 = 50;
+	pub const MaxClaimsPerCall: u32 = 100;
+	/// A new payout address takes effect after two days.
+	pub const PayoutChangeDelay: BlockNumber = 2 * DAYS;
+	/// Nodes can report on their contracts for free 20 times an hour.
+	pub const FreeReportPeriod: BlockNumber = HOURS;
+	pub const MaxFreeReportsPerPeriod: u32 = 20;
+	/// Users get an hour to confirm a farmer's counter-offer.
+	pub const OfferDuration: BlockNumber = HOURS;
+	/// The first payment of a contract has to cover at least a day, unless the farm says otherwise.
+	pub const MinimumPrepaidDuration: u64 = 24 * 60 * 60;
+	/// A node holds at most 100 funded contracts.
+	pub const MaxContractsPerNode: u32 = 100;
+}
+
 /// Configure the template pallet in pallets/template.
 impl pallet_template::Trait for Runtime {
 	type Event = Event;
     type Currency = Balances;
 	type RandomnessSource = RandomnessCollectiveFlip;
 	type AuthorityId = pallet_template::crypto::TestAuthId;
+	type HeartbeatInterval = HeartbeatInterval;
+	type BondPerUnit = BondPerUnit;
+	type SlashFraction = SlashFraction;
+	type OutageGracePeriod = OutageGracePeriod;
+	type SlashDowntime = SlashDowntime;
+	type ArbitrationOrigin = frame_system::EnsureRoot<AccountId>;
+	type ChallengeWindow = ChallengeWindow;
+	type StorageChallengeInterval = StorageChallengeInterval;
+	type StorageChallengeTimeout = StorageChallengeTimeout;
+	type AutoPayoutPeriod = AutoPayoutPeriod;
+	type MaxPayoutsPerBlock = MaxPayoutsPerBlock;
+	type MaxClaimsPerCall = MaxClaimsPerCall;
+	type PayoutChangeDelay = PayoutChangeDelay;
+	type FreeReportPeriod = FreeReportPeriod;
+	type MaxFreeReportsPerPeriod = MaxFreeReportsPerPeriod;
+	type OfferDuration = OfferDuration;
+	type MinimumPrepaidDuration = MinimumPrepaidDuration;
+	type MaxContractsPerNode = MaxContractsPerNode;
 }
 
 // Create the runtime by composing the FRAME pallets that were previously configured.
@@ -345,7 +422,10 @@ construct_runtime!(
 		TransactionPayment: pallet_transaction_payment::{Module, Storage},
 		Sudo: pallet_sudo::{Module, Call, Config<T>, Storage, Event<T>},
 		// Include the custom logic from the template pallet in the runtime.
-		TemplateModule: pallet_template::{Module, Call, Storage, Event<T>},
+		TemplateModule: pallet_template::{Module, Call, Storage, Event<T>, ValidateUnsigned},
+		TwinModule: pallet_twin::{Module, Call, Storage, Event<T>},
+		DidModule: pallet_did::{Module, Call, Storage, Event<T>},
+		OrganisationModule: pallet_organisation::{Module, Call, Storage, Event<T>},
 	}
 );
 
@@ -367,7 +447,7 @@ pub type SignedExtra = (
 	frame_system::CheckEra<Runtime>,
 	frame_system::CheckNonce<Runtime>,
 	frame_system::CheckWeight<Runtime>,
-	pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+	ChargeNodeReportFee,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
@@ -497,6 +577,12 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl pallet_did_runtime_api::DidApi<Block, AccountId, BlockNumber> for Runtime {
+		fn resolve(did: Vec<u8>) -> Option<pallet_did::DidDocument<AccountId, BlockNumber>> {
+			DidModule::resolve(&did)
+		}
+	}
+
 	impl pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi<Block, Balance> for Runtime {
 		fn query_info(
 			uxt: <Block as BlockT>::Extrinsic,
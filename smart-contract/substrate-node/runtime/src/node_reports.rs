@@ -0,0 +1,234 @@
+//! Fee handling for the reports nodes make on their contracts.
+//!
+//! Nodes would otherwise need a funded account just to call `contract_deployed`
+//! and `contract_cancelled`. This extension replaces `ChargeTransactionPayment`
+//! and waives the fee when a node reports on one of its own active contracts,
+//! as long as it stays within its fee-free reports for the period.
+
+use codec::{Decode, Encode};
+use pallet_transaction_payment::ChargeTransactionPayment;
+use sp_runtime::{
+	traits::{DispatchInfoOf, PostDispatchInfoOf, SignedExtension},
+	transaction_validity::{TransactionValidity, TransactionValidityError, ValidTransaction},
+	DispatchResult, RuntimeDebug,
+};
+use sp_std::prelude::*;
+
+use crate::{AccountId, Balance, Call, Runtime, TemplateModule};
+
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
+pub struct ChargeNodeReportFee(ChargeTransactionPayment<Runtime>);
+
+impl From<Balance> for ChargeNodeReportFee {
+	fn from(tip: Balance) -> Self {
+		ChargeNodeReportFee(ChargeTransactionPayment::<Runtime>::from(tip))
+	}
+}
+
+impl ChargeNodeReportFee {
+	fn is_free(who: &AccountId, call: &Call) -> bool {
+		match call {
+			Call::TemplateModule(call) => TemplateModule::is_free_report(who, call),
+			_ => false,
+		}
+	}
+}
+
+impl SignedExtension for ChargeNodeReportFee {
+	const IDENTIFIER: &'static str = "ChargeNodeReportFee";
+	type AccountId = AccountId;
+	type Call = Call;
+	type AdditionalSigned = ();
+	// `None` when the fee was waived
+	type Pre = Option<<ChargeTransactionPayment<Runtime> as SignedExtension>::Pre>;
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> TransactionValidity {
+		if Self::is_free(who, call) {
+			// Reserves the next slot of the allowance while the report waits in the pool
+			return Ok(ValidTransaction {
+				provides: vec![TemplateModule::free_report_tag(who)],
+				..Default::default()
+			});
+		}
+
+		self.0.validate(who, call, info, len)
+	}
+
+	fn pre_dispatch(
+		self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> Result<Self::Pre, TransactionValidityError> {
+		if Self::is_free(who, call) {
+			TemplateModule::note_free_report(who);
+			return Ok(None);
+		}
+
+		Ok(Some(self.0.pre_dispatch(who, call, info, len)?))
+	}
+
+	fn post_dispatch(
+		pre: Self::Pre,
+		info: &DispatchInfoOf<Self::Call>,
+		post_info: &PostDispatchInfoOf<Self::Call>,
+		len: usize,
+		result: &DispatchResult,
+	) -> Result<(), TransactionValidityError> {
+		match pre {
+			Some(pre) => ChargeTransactionPayment::<Runtime>::post_dispatch(pre, info, post_info, len, result),
+			None => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		Balances, BondPerUnit, MaxFreeReportsPerPeriod, Origin, System, Timestamp, TwinModule,
+	};
+	use frame_support::{assert_ok, weights::GetDispatchInfo};
+	use sp_core::{ed25519, Pair};
+
+	// What a contract on `volume()` costs per second at `prices()`
+	const PRICE_PER_SEC: Balance = 10_000_000_000;
+	const DAY: u64 = 24 * 60 * 60;
+
+	fn user() -> AccountId {
+		AccountId::from([1u8; 32])
+	}
+
+	fn farmer() -> AccountId {
+		AccountId::from([2u8; 32])
+	}
+
+	fn node_pair() -> ed25519::Pair {
+		ed25519::Pair::from_seed(&[3u8; 32])
+	}
+
+	fn node_account() -> AccountId {
+		AccountId::from(node_pair().public().0)
+	}
+
+	// The pallet's types keep their fields private, build them from their encoding
+	fn decoded<T: Decode>(value: impl Encode) -> T {
+		T::decode(&mut &value.encode()[..]).unwrap()
+	}
+
+	fn volume() -> pallet_template::VolumeType {
+		decoded((1u8, 1u64))
+	}
+
+	fn prices() -> pallet_template::ResourcePrice {
+		// currency, sru, hru, cru, nru, mru
+		decoded((0u64, 0u64, 36u64, 0u64, 0u64, 0u64))
+	}
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut storage = frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap();
+		pallet_balances::GenesisConfig::<Runtime> {
+			balances: vec![(user(), 1 << 70), (farmer(), 1 << 70)],
+		}
+		.assimilate_storage(&mut storage)
+		.unwrap();
+
+		let mut ext = sp_io::TestExternalities::new(storage);
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	// A contract of the user on the farmer's node, funded when asked, the node's account stays empty
+	fn setup_contract(funded: bool) -> u64 {
+		assert_ok!(Timestamp::set_timestamp(1_000_000));
+		assert_ok!(TwinModule::create_twin(Origin::signed(user()), None, None, vec![]));
+		assert_ok!(TwinModule::create_twin(Origin::signed(farmer()), None, None, vec![]));
+		assert_ok!(TemplateModule::create_farm(Origin::signed(farmer())));
+
+		let pair = node_pair();
+		let signature = pair.sign(&(0u64, farmer(), 0u64).encode());
+		assert_ok!(TemplateModule::register_node(Origin::signed(farmer()), 0, pair.public(), signature));
+		let node_id = TemplateModule::nodes_for_farm(0)[0].clone();
+
+		// cru, mru, sru, hru
+		assert_ok!(TemplateModule::bond(Origin::signed(farmer()), 0, BondPerUnit::get() * 220));
+		assert_ok!(TemplateModule::report_capacity(
+			Origin::signed(node_account()),
+			node_id.clone(),
+			decoded((4u64, 16u64, 100u64, 100u64)),
+		));
+
+		assert_ok!(TemplateModule::create_contract(Origin::signed(user()), node_id, volume()));
+		let reservation_id = TemplateModule::reservations_for_account(user())[0];
+		assert_ok!(TemplateModule::set_contract_price(Origin::signed(user()), reservation_id, prices(), farmer()));
+		if funded {
+			assert_ok!(TemplateModule::pay(Origin::signed(user()), reservation_id, PRICE_PER_SEC * DAY as Balance));
+		}
+
+		reservation_id
+	}
+
+	fn deployed(reservation_id: u64) -> Call {
+		Call::TemplateModule(pallet_template::Call::contract_deployed(reservation_id))
+	}
+
+	#[test]
+	fn a_node_reports_on_its_funded_contract_for_free() {
+		new_test_ext().execute_with(|| {
+			let call = deployed(setup_contract(true));
+			let info = call.get_dispatch_info();
+			assert_eq!(Balances::free_balance(&node_account()), 0);
+
+			// The pool holds one pending free report per slot of the allowance
+			let first = ChargeNodeReportFee::from(0).validate(&node_account(), &call, &info, 0).unwrap();
+			assert_eq!(first.provides, vec![TemplateModule::free_report_tag(&node_account())]);
+
+			assert!(ChargeNodeReportFee::from(0).pre_dispatch(&node_account(), &call, &info, 0).unwrap().is_none());
+			assert_eq!(TemplateModule::free_reports(&node_account()).1, 1);
+
+			// The next report takes the next slot
+			let next = ChargeNodeReportFee::from(0).validate(&node_account(), &call, &info, 0).unwrap();
+			assert_ne!(next.provides, first.provides);
+		});
+	}
+
+	#[test]
+	fn a_node_pays_once_its_free_reports_are_used_up() {
+		new_test_ext().execute_with(|| {
+			let call = deployed(setup_contract(true));
+			let info = call.get_dispatch_info();
+
+			for _ in 0..MaxFreeReportsPerPeriod::get() {
+				TemplateModule::note_free_report(&node_account());
+			}
+
+			assert!(!ChargeNodeReportFee::is_free(&node_account(), &call));
+			// The node's account can't pay the fee
+			assert!(ChargeNodeReportFee::from(0).validate(&node_account(), &call, &info, 0).is_err());
+			assert!(ChargeNodeReportFee::from(0).pre_dispatch(&node_account(), &call, &info, 0).is_err());
+		});
+	}
+
+	#[test]
+	fn reports_on_unfunded_contracts_are_not_free() {
+		new_test_ext().execute_with(|| {
+			let call = deployed(setup_contract(false));
+			let info = call.get_dispatch_info();
+
+			assert!(!ChargeNodeReportFee::is_free(&node_account(), &call));
+			assert!(ChargeNodeReportFee::from(0).validate(&node_account(), &call, &info, 0).is_err());
+			assert_eq!(TemplateModule::free_reports(&node_account()).1, 0);
+		});
+	}
+}